@@ -0,0 +1,6 @@
+-- the original table, which existing deployments already have
+CREATE TABLE IF NOT EXISTS cryptograms (
+    token INT PRIMARY KEY,
+    plaintext VARCHAR(160),
+    key VARCHAR(20)
+);
@@ -0,0 +1,3 @@
+-- running keys are entire quotes, and normalized quotes can be longer than 160 characters
+ALTER TABLE cryptograms ALTER COLUMN plaintext TYPE TEXT;
+ALTER TABLE cryptograms ALTER COLUMN key TYPE TEXT;
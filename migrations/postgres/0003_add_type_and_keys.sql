@@ -0,0 +1,3 @@
+ALTER TABLE cryptograms ADD COLUMN IF NOT EXISTS type TEXT;
+ALTER TABLE cryptograms ADD COLUMN IF NOT EXISTS ciphertext TEXT;
+ALTER TABLE cryptograms ADD COLUMN IF NOT EXISTS structured_key TEXT;
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS hints (
+    token INT REFERENCES cryptograms (token),
+    kind TEXT,
+    position INT,
+    penalty INT
+);
+
+CREATE TABLE IF NOT EXISTS tests (
+    id SERIAL PRIMARY KEY,
+    seed INT
+);
+
+CREATE TABLE IF NOT EXISTS test_questions (
+    test_id INT REFERENCES tests (id),
+    number INT,
+    token INT REFERENCES cryptograms (token),
+    points INT,
+    hint TEXT,
+    PRIMARY KEY (test_id, number)
+);
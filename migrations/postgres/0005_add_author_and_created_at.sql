@@ -0,0 +1,2 @@
+ALTER TABLE cryptograms ADD COLUMN IF NOT EXISTS author TEXT;
+ALTER TABLE cryptograms ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
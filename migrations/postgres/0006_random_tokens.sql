@@ -0,0 +1,13 @@
+-- tokens are random strings now. Integer tokens issued before are kept as their decimal digits,
+-- so they can still be looked up.
+ALTER TABLE hints DROP CONSTRAINT IF EXISTS hints_token_fkey;
+ALTER TABLE test_questions DROP CONSTRAINT IF EXISTS test_questions_token_fkey;
+
+ALTER TABLE cryptograms ALTER COLUMN token TYPE TEXT USING token::TEXT;
+ALTER TABLE hints ALTER COLUMN token TYPE TEXT USING token::TEXT;
+ALTER TABLE test_questions ALTER COLUMN token TYPE TEXT USING token::TEXT;
+
+ALTER TABLE hints
+    ADD CONSTRAINT hints_token_fkey FOREIGN KEY (token) REFERENCES cryptograms (token);
+ALTER TABLE test_questions
+    ADD CONSTRAINT test_questions_token_fkey FOREIGN KEY (token) REFERENCES cryptograms (token);
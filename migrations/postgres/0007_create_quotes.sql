@@ -0,0 +1,13 @@
+-- quotes used to be read from QUOTES_FILE on every start. Tags are a JSON list, like
+-- `structured_key`.
+CREATE TABLE quotes (
+    id SERIAL PRIMARY KEY,
+    text TEXT NOT NULL UNIQUE,
+    author TEXT,
+    genre TEXT,
+    source TEXT,
+    language TEXT,
+    tags TEXT NOT NULL DEFAULT '[]',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,7 @@
+-- the quotes served to each history, such as `session:abc`, so they are not served again
+CREATE TABLE quote_history (
+    history TEXT NOT NULL,
+    quote_id INT NOT NULL REFERENCES quotes (id),
+    served_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (history, quote_id)
+);
@@ -0,0 +1,30 @@
+-- databases created before migrations already have these tables
+CREATE TABLE IF NOT EXISTS cryptograms (
+    token INTEGER PRIMARY KEY,
+    plaintext TEXT NOT NULL,
+    key TEXT,
+    type TEXT,
+    ciphertext TEXT,
+    structured_key TEXT
+);
+
+CREATE TABLE IF NOT EXISTS hints (
+    token INTEGER REFERENCES cryptograms (token),
+    kind TEXT,
+    position INTEGER,
+    penalty INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS tests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    seed INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS test_questions (
+    test_id INTEGER REFERENCES tests (id),
+    number INTEGER,
+    token INTEGER REFERENCES cryptograms (token),
+    points INTEGER,
+    hint TEXT,
+    PRIMARY KEY (test_id, number)
+);
@@ -0,0 +1,3 @@
+-- SQLite can't add a column with a non-constant default, so rows are timestamped on insert
+ALTER TABLE cryptograms ADD COLUMN author TEXT;
+ALTER TABLE cryptograms ADD COLUMN created_at TEXT;
@@ -0,0 +1,49 @@
+-- tokens are random strings now. Integer tokens issued before are kept as their decimal digits,
+-- so they can still be looked up.
+--
+-- SQLite can't change the type of a column, so the tables are rebuilt. Renaming `cryptograms`
+-- points the old foreign keys at the old table, so the old tables can be dropped afterwards.
+ALTER TABLE test_questions RENAME TO test_questions_old;
+ALTER TABLE hints RENAME TO hints_old;
+ALTER TABLE cryptograms RENAME TO cryptograms_old;
+
+CREATE TABLE cryptograms (
+    token TEXT PRIMARY KEY,
+    plaintext TEXT NOT NULL,
+    key TEXT,
+    type TEXT,
+    ciphertext TEXT,
+    structured_key TEXT,
+    author TEXT,
+    created_at TEXT
+);
+
+CREATE TABLE hints (
+    token TEXT REFERENCES cryptograms (token),
+    kind TEXT,
+    position INTEGER,
+    penalty INTEGER
+);
+
+CREATE TABLE test_questions (
+    test_id INTEGER REFERENCES tests (id),
+    number INTEGER,
+    token TEXT REFERENCES cryptograms (token),
+    points INTEGER,
+    hint TEXT,
+    PRIMARY KEY (test_id, number)
+);
+
+INSERT INTO cryptograms
+SELECT CAST(token AS TEXT), plaintext, key, type, ciphertext, structured_key, author, created_at
+FROM cryptograms_old;
+
+INSERT INTO hints
+SELECT CAST(token AS TEXT), kind, position, penalty FROM hints_old;
+
+INSERT INTO test_questions
+SELECT test_id, number, CAST(token AS TEXT), points, hint FROM test_questions_old;
+
+DROP TABLE test_questions_old;
+DROP TABLE hints_old;
+DROP TABLE cryptograms_old;
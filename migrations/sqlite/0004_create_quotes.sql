@@ -0,0 +1,13 @@
+-- quotes used to be read from QUOTES_FILE on every start. Tags are a JSON list, like
+-- `structured_key`.
+CREATE TABLE quotes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL UNIQUE,
+    author TEXT,
+    genre TEXT,
+    source TEXT,
+    language TEXT,
+    tags TEXT NOT NULL DEFAULT '[]',
+    enabled INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
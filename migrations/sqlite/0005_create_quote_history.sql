@@ -0,0 +1,7 @@
+-- the quotes served to each history, such as `session:abc`, so they are not served again
+CREATE TABLE quote_history (
+    history TEXT NOT NULL,
+    quote_id INTEGER NOT NULL REFERENCES quotes (id),
+    served_at TEXT NOT NULL,
+    PRIMARY KEY (history, quote_id)
+);
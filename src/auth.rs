@@ -0,0 +1,131 @@
+//! This module decides what each API request is allowed to do.
+//!
+//! Requests send an API key as a bearer token, in an `Authorization: Bearer KEY` header. Each key
+//! configured in `admin_keys`, `proctor_keys` or `student_keys` gives its [`Role`]:
+//!
+//! - students can request cryptograms and tests, ask for hints and submit answers,
+//! - proctors can also create tests, read answers and use the solver,
+//! - admins can also list, add, edit and disable the quotes.
+//!
+//! If no admin or proctor keys are configured, every request is refused, unless `insecure_no_auth`
+//! is set to turn authentication off and make every request an admin. That is only meant for
+//! running locally, and the server does not start without one or the other.
+
+use super::config::Config;
+use std::{error::Error, fmt};
+
+/// What an API key is allowed to do. Each role can do everything the roles before it can.
+#[derive(GraphQLEnum, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Solves cryptograms, but can't see their answers.
+    Student,
+    /// Runs tests, and can see answers.
+    Proctor,
+    /// Manages the quotes that cryptograms are made from.
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A request without a valid API key.
+#[derive(Debug)]
+pub struct AuthError {
+    error: &'static str,
+}
+
+impl Error for AuthError {}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AuthError: {}", self.error)
+    }
+}
+
+/// Compare `a` and `b` in time that only depends on their lengths, so keys can't be guessed a
+/// byte at a time by timing requests.
+fn keys_equal(a: &str, b: &str) -> bool {
+    let diff = a
+        .bytes()
+        .zip(b.bytes())
+        .fold(0, |acc, (a, b)| acc | (a ^ b));
+    a.len() == b.len() && diff == 0
+}
+
+/// The role of a request sent with `key`, or an error if the key is missing or unknown.
+pub(crate) fn authenticate(config: &Config, key: Option<&str>) -> Result<Role, AuthError> {
+    if !config.auth_enabled() {
+        return if config.insecure_no_auth {
+            Ok(Role::Admin)
+        } else {
+            Err(AuthError {
+                error: "No API keys are configured",
+            })
+        };
+    }
+
+    let key = key.ok_or(AuthError {
+        error: "An API key is required",
+    })?;
+
+    if config.admin_keys.iter().any(|k| keys_equal(k, key)) {
+        Ok(Role::Admin)
+    } else if config.proctor_keys.iter().any(|k| keys_equal(k, key)) {
+        Ok(Role::Proctor)
+    } else if config.student_keys.iter().any(|k| keys_equal(k, key)) {
+        Ok(Role::Student)
+    } else {
+        Err(AuthError {
+            error: "Unknown API key",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authenticate() {
+        let config = Config {
+            admin_keys: vec!["admin".into()],
+            proctor_keys: vec!["proctor".into()],
+            student_keys: vec!["student".into(), "student2".into()],
+            ..Config::default()
+        };
+
+        assert_eq!(authenticate(&config, Some("admin")).unwrap(), Role::Admin);
+        assert_eq!(
+            authenticate(&config, Some("proctor")).unwrap(),
+            Role::Proctor
+        );
+        assert_eq!(
+            authenticate(&config, Some("student2")).unwrap(),
+            Role::Student
+        );
+        assert!(authenticate(&config, Some("studen")).is_err());
+        assert!(authenticate(&config, None).is_err());
+    }
+
+    #[test]
+    fn test_authenticate_keyless() {
+        // without keys, every request is refused
+        assert!(authenticate(&Config::default(), None).is_err());
+        assert!(authenticate(&Config::default(), Some("admin")).is_err());
+
+        let config = Config {
+            insecure_no_auth: true,
+            ..Config::default()
+        };
+        assert_eq!(authenticate(&config, None).unwrap(), Role::Admin);
+    }
+
+    #[test]
+    fn test_roles() {
+        assert!(Role::Admin > Role::Proctor);
+        assert!(Role::Proctor > Role::Student);
+    }
+}
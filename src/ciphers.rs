@@ -1,34 +1,44 @@
 //! This module contains the implementation of the various ciphers.
 #![warn(missing_docs)]
 
+mod alphabet;
+mod book;
 mod cryptarithm;
 mod errors;
 mod hill;
+mod key;
 mod morse;
+mod polybius;
+mod running_key;
 mod substitution;
 
+use super::config;
 use super::cryptogram::Type;
 use super::cryptogram::Type::{
-    Aristocrat, Caesar, Hill, Identity, Morbit, Patristocrat, PatristocratK1, PatristocratK2, Rot13,
+    Aristocrat, Book, Caesar, Hill, Identity, Morbit, Patristocrat, PatristocratK1, PatristocratK2,
+    Pigpen, Polybius, Rot13, RunningKey, TapCode,
 };
+use super::quotes::{self, Quote};
+pub(crate) use alphabet::Alphabet;
 pub(crate) use errors::{CipherError, CipherResult, ErrorKind};
+pub use key::Key;
 use lazy_static::lazy_static;
 use rand::prelude::*;
 
 lazy_static! {
     /// Stores words suitable for use as keys in patristocrats or operands in cryptarithms
     static ref WORDS: Vec<String> = {
-        let words_file =
-            std::env::var("WORDS_FILE").expect("Environment variable WORDS_FILE must be set.");
+        let config = config::get();
+        let words_file = config.words_file.as_ref().expect("WORDS_FILE must be set.");
 
         log::info!("Loading words from {:?}", words_file);
         let contents = std::fs::read_to_string(words_file).unwrap();
 
+        let lengths = config.min_word_length..=config.max_word_length;
         let words: Vec<_> = contents.trim()
             .split(',')
             .filter_map(|s| {
-                // avoid words like "the" or "what" and don't want long, obscure words
-                if 4 < s.len() && s.len() < 8 {
+                if lengths.contains(&s.len()) {
                     Some(s.to_string())
                 } else {
                     None
@@ -42,25 +52,9 @@ lazy_static! {
     };
 }
 
-/// Lowercase alphabet.
-const ALPHABET: [u8; 26] = *b"abcdefghijklmnopqrstuvwxyz";
-
-/// Adjust the case of ord to match the case of `to_match`
-const fn match_case(ord: u8, to_match: u8) -> u8 {
-    let is_lower = (to_match >> 5) & 1;
-    ord & !(1 << 5) | (is_lower << 5)
-}
-
-/// Convenience function to shift `b` by `by` places.
-const fn shift_letter(b: u8, by: u8) -> u8 {
-    let offset = match_case(b'a', b);
-
-    (b - offset + by) % 26 + offset
-}
-
 /// Returns the input string unchanged.
 fn identity(s: &str) -> Cipher {
-    Cipher::new(s.to_string(), None)
+    Cipher::new(s.to_string(), Key::default())
 }
 
 /// The type returned by the various encryption functions in this library.
@@ -69,93 +63,114 @@ pub struct Cipher {
     /// The ciphertext
     pub ciphertext: String,
     /// The key
-    pub key: Option<String>,
+    pub key: Key,
 }
 
 impl Cipher {
-    fn new(ciphertext: String, key: Option<String>) -> Self {
+    fn new(ciphertext: String, key: Key) -> Self {
         Self { ciphertext, key }
     }
 
     /// Wrapper function to call a specific cipher by [`Type`].
-    pub(crate) fn encrypt(
+    ///
+    /// Letters are looked up in `alphabet`, except for ciphers with a fixed symbol set such as
+    /// [`Morbit`] and [`Pigpen`]. [`Book`] and [`RunningKey`] are keyed by `quotes`. All
+    /// randomness comes from `rng`, so a seeded `rng` always gives the same result.
+    pub(crate) fn encrypt<R>(
         plaintext: &str,
         cipher_type: Type,
         key: Option<String>,
-    ) -> CipherResult<Self> {
-        let rng = &mut thread_rng();
-
+        alphabet: &Alphabet,
+        quotes: &[Quote],
+        rng: &mut R,
+    ) -> CipherResult<Self>
+    where
+        R: Rng + ?Sized,
+    {
         Ok(match cipher_type {
-            Aristocrat => substitution::aristocrat(plaintext, rng),
-            Caesar => substitution::caeser(plaintext, rng),
+            Aristocrat => substitution::aristocrat(plaintext, alphabet, rng),
+            Book => book::book(plaintext, &quotes::texts(quotes), alphabet, rng)?,
+            Caesar => substitution::caeser(plaintext, alphabet, rng),
             // Cryptarithm => cryptarithm::cryptarithm(&mut rng),
-            Hill => hill::hill(plaintext, key, rng)?,
+            Hill => hill::hill(plaintext, key, alphabet, rng)?,
             Identity => identity(plaintext),
-            Morbit => morse::morbit(plaintext, key),
-            Patristocrat => substitution::patristocrat(plaintext, rng),
-            PatristocratK1 => substitution::patristocrat_k1(plaintext, key, rng),
-            PatristocratK2 => substitution::patristocrat_k2(plaintext, key, rng),
-            Rot13 => substitution::rot13(plaintext),
+            Morbit => morse::morbit(plaintext, key, rng)?,
+            Patristocrat => substitution::patristocrat(plaintext, alphabet, rng),
+            PatristocratK1 => substitution::patristocrat_k1(plaintext, key, alphabet, rng),
+            PatristocratK2 => substitution::patristocrat_k2(plaintext, key, alphabet, rng),
+            Pigpen => polybius::pigpen(plaintext),
+            Polybius => polybius::polybius(plaintext, key, alphabet),
+            Rot13 => substitution::rot13(plaintext, alphabet),
+            RunningKey => {
+                running_key::running_key(plaintext, key, &quotes::texts(quotes), alphabet, rng)?
+            }
+            TapCode => polybius::tap_code(plaintext, alphabet),
         })
     }
+
+    /// Decrypt `ciphertext` of a specific [`Type`] with `key`.
+    ///
+    /// Keys are given the same way as to [`Cipher::encrypt`], except that Caesar takes its shift
+    /// and Aristocrat and Patristocrat take their mapping, as in [`Key::mapping`]. Only ciphers
+    /// that can be decrypted from a key alone are supported.
+    pub(crate) fn decrypt(
+        ciphertext: &str,
+        cipher_type: Type,
+        key: Option<&str>,
+        alphabet: &Alphabet,
+    ) -> CipherResult<String> {
+        use substitution::SubstitutionKey;
+
+        let key = || {
+            key.ok_or_else(|| {
+                CipherError::new(
+                    ErrorKind::InvalidKey,
+                    format!("A key is needed to decrypt {cipher_type}"),
+                )
+            })
+        };
+
+        match cipher_type {
+            Identity => Ok(ciphertext.to_string()),
+            Rot13 => substitution::decrypt(
+                ciphertext,
+                SubstitutionKey::Shift(alphabet.len() / 2),
+                alphabet,
+            ),
+            Caesar => {
+                let shift = key()?.trim().parse().map_err(|_| {
+                    CipherError::new(ErrorKind::InvalidKey, "Shift must be a number".into())
+                })?;
+                substitution::decrypt(ciphertext, SubstitutionKey::Shift(shift), alphabet)
+            }
+            Aristocrat | Patristocrat => {
+                substitution::decrypt(ciphertext, SubstitutionKey::Mapping(key()?), alphabet)
+            }
+            PatristocratK1 => {
+                substitution::decrypt(ciphertext, SubstitutionKey::K1(key()?), alphabet)
+            }
+            PatristocratK2 => {
+                substitution::decrypt(ciphertext, SubstitutionKey::K2(key()?), alphabet)
+            }
+            Hill => hill::decrypt(ciphertext, key()?, alphabet),
+            RunningKey => running_key::decrypt(ciphertext, key()?, alphabet),
+            Book | Morbit | Pigpen | Polybius | TapCode => Err(CipherError::new(
+                ErrorKind::InvalidCiphertext,
+                format!("Decrypting {cipher_type} is not supported"),
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_match_case_same_case() {
-        assert_eq!(match_case(b'a', b'a'), b'a');
-    }
-
-    #[test]
-    fn test_match_case_lower() {
-        let inps: Vec<_> = (b'a'..=b'z').collect();
-
-        for inp in inps {
-            assert_eq!(
-                match_case(inp, inp.to_ascii_uppercase()),
-                inp.to_ascii_uppercase()
-            );
-        }
-    }
-
-    #[test]
-    fn test_match_case_upper() {
-        let inps: Vec<_> = (b'A'..=b'Z').collect();
-
-        for inp in inps {
-            assert_eq!(
-                match_case(inp, inp.to_ascii_lowercase()),
-                inp.to_ascii_lowercase()
-            );
-        }
-    }
-
-    #[test]
-    fn test_shift_letter() {
-        // build a rotated alphabet
-        let mut expected = Vec::new();
-        for i in b't'..=b'z' {
-            expected.push(i);
-        }
-        for i in b'a'..b't' {
-            expected.push(i);
-        }
-
-        println!("{expected:?}");
-        let initial = b't';
-        for i in 0..26 {
-            assert_eq!(shift_letter(initial, i), expected[i as usize]);
-        }
-    }
-
     #[test]
     fn test_identity() {
         assert_eq!(
             identity("abcdefghijklmnopqrstuvwxyz").ciphertext,
-            "abcdefghijklmnopqrstuvwxyz".into()
+            String::from("abcdefghijklmnopqrstuvwxyz")
         )
     }
 }
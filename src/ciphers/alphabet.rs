@@ -0,0 +1,163 @@
+//! Alphabets used by the ciphers.
+//!
+//! Every cipher looks letters up through an [`Alphabet`] instead of assuming the 26 letters of
+//! the English alphabet, so the same implementation works for 25-letter squares, 36-symbol
+//! squares and non-English alphabets.
+
+/// An ordered set of symbols that a cipher operates on.
+///
+/// Symbols are stored lowercase. Lookups fold case and then apply merges, so with "j" merged into
+/// "i", "J", "j", "I" and "i" are all treated as "i".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Alphabet {
+    symbols: Vec<char>,
+    merges: Vec<(char, char)>,
+}
+
+impl Alphabet {
+    /// Create an alphabet from `symbols`, in order.
+    pub fn new(symbols: &str) -> Self {
+        Self {
+            symbols: symbols.chars().map(to_lower).collect(),
+            merges: Vec::new(),
+        }
+    }
+
+    /// The 26 letters of the English alphabet.
+    pub fn english() -> Self {
+        Self::new("abcdefghijklmnopqrstuvwxyz")
+    }
+
+    /// The English alphabet with "j" merged into "i", for 5x5 squares.
+    pub fn latin25() -> Self {
+        Self::english().with_merge('j', 'i')
+    }
+
+    /// Treat `from` as `to`. `from` is removed from the alphabet if present.
+    pub fn with_merge(mut self, from: char, to: char) -> Self {
+        let from = to_lower(from);
+        self.symbols.retain(|&c| c != from);
+        self.merges.push((from, to_lower(to)));
+        self
+    }
+
+    /// The number of symbols in the alphabet.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// The symbols of the alphabet, in order.
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+
+    /// Returns the symbol at `index`.
+    pub fn symbol(&self, index: usize) -> char {
+        self.symbols[index]
+    }
+
+    /// Lowercase `c` and apply merges.
+    pub fn fold(&self, c: char) -> char {
+        let c = to_lower(c);
+        self.merges
+            .iter()
+            .find(|&&(from, _)| from == c)
+            .map_or(c, |&(_, to)| to)
+    }
+
+    /// Returns the index of `c` after folding, if it is in the alphabet.
+    pub fn index_of(&self, c: char) -> Option<usize> {
+        let c = self.fold(c);
+        self.symbols.iter().position(|&s| s == c)
+    }
+
+    /// Adjust the case of `c` to match the case of `to_match`.
+    pub fn match_case(c: char, to_match: char) -> char {
+        if to_match.is_uppercase() {
+            to_upper(c)
+        } else if to_match.is_lowercase() {
+            to_lower(c)
+        } else {
+            c
+        }
+    }
+
+    /// Shift `c` by `by` places, keeping its case.
+    ///
+    /// Returns `None` if `c` is not in the alphabet.
+    pub fn shift(&self, c: char, by: usize) -> Option<char> {
+        self.index_of(c)
+            .map(|i| Self::match_case(self.symbols[(i + by) % self.len()], c))
+    }
+}
+
+fn to_lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn to_upper(c: char) -> char {
+    c.to_uppercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_case_same_case() {
+        assert_eq!(Alphabet::match_case('a', 'a'), 'a');
+    }
+
+    #[test]
+    fn test_match_case_lower() {
+        for inp in 'a'..='z' {
+            assert_eq!(
+                Alphabet::match_case(inp, inp.to_ascii_uppercase()),
+                inp.to_ascii_uppercase()
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_case_upper() {
+        for inp in 'A'..='Z' {
+            assert_eq!(
+                Alphabet::match_case(inp, inp.to_ascii_lowercase()),
+                inp.to_ascii_lowercase()
+            );
+        }
+    }
+
+    #[test]
+    fn test_shift() {
+        // build a rotated alphabet
+        let expected: Vec<_> = ('t'..='z').chain('a'..'t').collect();
+
+        let alphabet = Alphabet::english();
+        for (i, &c) in expected.iter().enumerate() {
+            assert_eq!(alphabet.shift('t', i), Some(c));
+        }
+        assert_eq!(alphabet.shift('Y', 3), Some('B'));
+        assert_eq!(alphabet.shift('!', 3), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let alphabet = Alphabet::latin25();
+
+        assert_eq!(alphabet.len(), 25);
+        assert_eq!(alphabet.index_of('J'), alphabet.index_of('i'));
+    }
+
+    #[test]
+    fn test_spanish() {
+        let alphabet = Alphabet::new("abcdefghijklmnñopqrstuvwxyz")
+            .with_merge('á', 'a')
+            .with_merge('é', 'e');
+
+        assert_eq!(alphabet.len(), 27);
+        assert_eq!(alphabet.index_of('Ñ'), Some(14));
+        assert_eq!(alphabet.index_of('é'), Some(4));
+        assert_eq!(alphabet.shift('z', 1), Some('a'));
+    }
+}
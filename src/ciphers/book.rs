@@ -0,0 +1,111 @@
+//! Book cipher.
+//!
+//! The "book" is every quote in the quote corpus, in order. Each word of the plaintext is encoded
+//! as the 1-indexed position of the quote and the word within that quote where the word appears.
+//! Words are split on whitespace and compared ignoring case and anything not in the alphabet.
+
+use super::{Alphabet, Cipher, Key};
+use super::{CipherError, CipherResult, ErrorKind};
+use rand::prelude::*;
+use std::collections::HashMap;
+
+fn normalize_word(word: &str, alphabet: &Alphabet) -> String {
+    word.chars()
+        .filter(|&c| alphabet.index_of(c).is_some())
+        .map(|c| alphabet.fold(c))
+        .collect()
+}
+
+/// Book Cipher
+///
+/// Each plaintext word is replaced by "(quote, word)", chosen randomly among the places the word
+/// appears in `quotes`. If the plaintext is itself one of the quotes, other quotes are preferred so
+/// the ciphertext does not simply point at the answer. Words with no letters are dropped.
+pub(super) fn book<R>(
+    s: &str,
+    quotes: &[&str],
+    alphabet: &Alphabet,
+    rng: &mut R,
+) -> CipherResult<Cipher>
+where
+    R: Rng + ?Sized,
+{
+    // map each word to every (quote, word) position it appears at
+    let mut positions: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for (i, quote) in quotes.iter().enumerate() {
+        for (j, word) in quote.split_whitespace().enumerate() {
+            let word = normalize_word(word, alphabet);
+            if !word.is_empty() {
+                positions.entry(word).or_default().push((i, j));
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for word in s.split_whitespace() {
+        let normalized = normalize_word(word, alphabet);
+        if normalized.is_empty() {
+            continue;
+        }
+
+        let all = positions.get(&normalized).map_or(&[][..], Vec::as_slice);
+        let others: Vec<_> = all.iter().filter(|&&(i, _)| quotes[i] != s).collect();
+
+        let position = if others.is_empty() {
+            all.choose(rng)
+        } else {
+            others.choose(rng).copied()
+        };
+
+        match position {
+            Some((i, j)) => out.push(format!("({}, {})", i + 1, j + 1)),
+            None => {
+                return Err(CipherError::new(
+                    ErrorKind::InvalidPlaintext,
+                    format!("{word:?} does not appear in the book"),
+                ))
+            }
+        }
+    }
+
+    Ok(Cipher::new(out.join(" "), Key::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    static BOOK: &[&str] = &["The cat sat.", "On the mat, a cat!"];
+
+    #[test]
+    fn test_book() {
+        let res = book(
+            "Sat on a mat",
+            BOOK,
+            &Alphabet::english(),
+            &mut StepRng::new(0, 1),
+        )
+        .unwrap();
+
+        assert_eq!(res.ciphertext, "(1, 3) (2, 1) (2, 4) (2, 3)");
+    }
+
+    #[test]
+    fn test_book_prefers_other_quotes() {
+        let res = book(
+            "The cat sat.",
+            BOOK,
+            &Alphabet::english(),
+            &mut StepRng::new(0, 1),
+        )
+        .unwrap();
+
+        assert_eq!(res.ciphertext, "(2, 2) (2, 5) (1, 3)");
+    }
+
+    #[test]
+    fn test_book_missing_word() {
+        assert!(book("dog", BOOK, &Alphabet::english(), &mut StepRng::new(0, 1)).is_err());
+    }
+}
@@ -17,8 +17,8 @@ use std::sync::{
     Mutex,
 };
 
-use super::Cipher;
 use super::WORDS;
+use super::{Cipher, Key};
 
 static mut STATS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
 
@@ -238,7 +238,7 @@ pub(super) fn cryptarithm<R: Rng + ?Sized>(rng: &mut R) -> Cipher {
                     unsafe {
                         log::debug!("Tries: {:?}", STATS);
                     }
-                    return Cipher::new(cryptarithm, None);
+                    return Cipher::new(cryptarithm, Key::default());
                 }
             }
         }
@@ -1,14 +1,59 @@
+//! Errors of the ciphers and the API.
+//!
+//! Every error has an [`ErrorKind`], which GraphQL clients get in the error's extensions:
+//!
+//! ```json
+//! {
+//!   "message": "Key length must be a perfect square",
+//!   "extensions": { "code": "INVALID_KEY", "field": "key" }
+//! }
+//! ```
+//!
+//! `code` is one of the codes documented on [`ErrorKind`]. `field` is the path of the argument
+//! that caused the error, such as `key` or `questions.2.difficulty` for the third question of a
+//! test, and is left out when no argument is to blame.
+
+use juniper::{FieldError, IntoFieldError, Object, Value};
 use std::{error::Error, fmt};
 
 #[derive(Debug)]
 pub(crate) struct CipherError {
     kind: ErrorKind,
     error: String,
+    /// The argument that caused the error.
+    field: Option<String>,
 }
 
 impl CipherError {
+    /// Create an error, blaming the argument that errors of `kind` usually come from.
     pub fn new(kind: ErrorKind, error: String) -> Self {
-        Self { kind, error }
+        Self {
+            kind,
+            error,
+            field: kind.field().map(String::from),
+        }
+    }
+
+    /// Blame the argument `field` instead.
+    #[must_use]
+    pub fn with_field(self, field: &str) -> Self {
+        Self {
+            field: Some(field.into()),
+            ..self
+        }
+    }
+
+    /// Nest the blamed argument under `parent`, such as an item of a list argument.
+    #[must_use]
+    pub fn within(self, parent: &str) -> Self {
+        let field = match &self.field {
+            Some(field) => format!("{parent}.{field}"),
+            None => parent.into(),
+        };
+        Self {
+            field: Some(field),
+            ..self
+        }
     }
 }
 
@@ -20,17 +65,116 @@ impl fmt::Display for CipherError {
     }
 }
 
-#[derive(Debug)]
+/// GraphQL errors have the message, and the code and field in their extensions.
+impl IntoFieldError for CipherError {
+    fn into_field_error(self) -> FieldError {
+        let mut extensions = Object::with_capacity(2);
+        extensions.add_field("code", Value::from(self.kind.code()));
+        if let Some(field) = self.field {
+            extensions.add_field("field", Value::from(field));
+        }
+
+        FieldError::new(self.error, Value::Object(extensions))
+    }
+}
+
+/// What went wrong, so callers can handle errors without reading their messages.
+///
+/// The code of each kind, in GraphQL error extensions, is its name in screaming snake case.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) enum ErrorKind {
-    KeyError,
+    /// `INVALID_KEY`: the key is missing or malformed, such as having the wrong length.
+    InvalidKey,
+    /// `NON_INVERTIBLE_KEY`: the Hill key matrix has no inverse, so it can't be decrypted.
+    NonInvertibleKey,
+    /// `INVALID_PLAINTEXT`: the plaintext can't be encrypted with the chosen cipher.
+    InvalidPlaintext,
+    /// `INVALID_CIPHERTEXT`: the ciphertext can't be decrypted.
+    InvalidCiphertext,
+    /// `INVALID_DIFFICULTY`: no cryptogram can be made in the requested difficulty range.
+    InvalidDifficulty,
+    /// `INVALID_ARGUMENT`: another argument is invalid, such as a test with no questions.
+    InvalidArgument,
+    /// `NO_QUOTE_AVAILABLE`: no quote fits the request, such as when there are no quotes of the
+    /// requested length.
+    NoQuoteAvailable,
+    /// `UNKNOWN_TOKEN`: no cryptogram has the token.
+    UnknownToken,
+    /// `UNKNOWN_TEST`: no test has the ID.
+    UnknownTest,
+    /// `UNKNOWN_QUOTE`: no quote has the ID.
+    UnknownQuote,
+    /// `NO_HINTS_LEFT`: every hint of the requested kind was already given.
+    NoHintsLeft,
+    /// `FORBIDDEN`: the API key's role is not allowed to do this. See [`crate::auth`].
+    Forbidden,
+    /// `STORAGE_ERROR`: an internal error, where the store failed. Retrying may work.
+    StorageError,
 }
 
-impl fmt::Display for ErrorKind {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl ErrorKind {
+    /// The machine readable code of the kind.
+    pub fn code(self) -> &'static str {
         match self {
-            ErrorKind::KeyError => write!(f, "KeyError"),
+            ErrorKind::InvalidKey => "INVALID_KEY",
+            ErrorKind::NonInvertibleKey => "NON_INVERTIBLE_KEY",
+            ErrorKind::InvalidPlaintext => "INVALID_PLAINTEXT",
+            ErrorKind::InvalidCiphertext => "INVALID_CIPHERTEXT",
+            ErrorKind::InvalidDifficulty => "INVALID_DIFFICULTY",
+            ErrorKind::InvalidArgument => "INVALID_ARGUMENT",
+            ErrorKind::NoQuoteAvailable => "NO_QUOTE_AVAILABLE",
+            ErrorKind::UnknownToken => "UNKNOWN_TOKEN",
+            ErrorKind::UnknownTest => "UNKNOWN_TEST",
+            ErrorKind::UnknownQuote => "UNKNOWN_QUOTE",
+            ErrorKind::NoHintsLeft => "NO_HINTS_LEFT",
+            ErrorKind::Forbidden => "FORBIDDEN",
+            ErrorKind::StorageError => "STORAGE_ERROR",
         }
     }
+
+    /// The argument that errors of this kind usually come from.
+    fn field(self) -> Option<&'static str> {
+        match self {
+            ErrorKind::InvalidKey | ErrorKind::NonInvertibleKey => Some("key"),
+            ErrorKind::InvalidPlaintext => Some("plaintext"),
+            ErrorKind::InvalidCiphertext => Some("ciphertext"),
+            ErrorKind::InvalidDifficulty => Some("difficulty"),
+            ErrorKind::UnknownToken => Some("token"),
+            ErrorKind::UnknownTest | ErrorKind::UnknownQuote => Some("id"),
+            ErrorKind::NoHintsLeft => Some("kind"),
+            ErrorKind::InvalidArgument
+            | ErrorKind::NoQuoteAvailable
+            | ErrorKind::Forbidden
+            | ErrorKind::StorageError => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
 }
 
 pub(crate) type CipherResult<T> = Result<T, CipherError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        let e = CipherError::new(ErrorKind::InvalidKey, "Bad key".into());
+        assert_eq!(e.field.as_deref(), Some("key"));
+
+        let e = e.within("questions.2");
+        assert_eq!(e.field.as_deref(), Some("questions.2.key"));
+
+        let e =
+            CipherError::new(ErrorKind::NoQuoteAvailable, "No quotes".into()).within("questions.0");
+        assert_eq!(e.field.as_deref(), Some("questions.0"));
+
+        let e = CipherError::new(ErrorKind::InvalidArgument, "Bad".into()).with_field("questions");
+        assert_eq!(e.field.as_deref(), Some("questions"));
+    }
+}
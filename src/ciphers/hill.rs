@@ -6,9 +6,11 @@
 //! and a key, whose length must be a perfect square, the encryption is done as follows.
 //! First the key is turned into an `NxN` matrix, where `N` is the square root of the length of the
 //! key.
-//! Then, each substring of `N` elements in the plaintext is multiplied by the key mod 26.
+//! Then, each substring of `N` elements in the plaintext is multiplied by the key mod 26, or the
+//! length of the alphabet in general.
 //!
-//! If the plaintext is not a multiple of `N`, the plaintext is padded with "z" until it is.
+//! If the plaintext is not a multiple of `N`, the plaintext is padded with "z", the last letter of
+//! the alphabet, until it is.
 //!
 //! For example, if wanted to encrypt the plaintext "abc" with the key "abcd", we would start by
 //! first computing `N`. In this case, `N=2`.
@@ -26,46 +28,44 @@
 
 use std::cmp::Ordering;
 
-use super::Cipher;
+use super::{Alphabet, Cipher, Key};
 use super::{CipherError, CipherResult, ErrorKind};
 use rand::prelude::*;
 
 const KEY_LENGTH: usize = 4;
 
-fn generate_key<R>(rng: &mut R) -> Vec<u8>
+fn generate_key<R>(alphabet: &Alphabet, rng: &mut R) -> String
 where
     R: Rng + ?Sized,
 {
-    let t: Vec<_> = (b'a'..=b'z').collect();
-    t.choose_multiple(rng, KEY_LENGTH).copied().collect()
+    alphabet
+        .symbols()
+        .choose_multiple(rng, KEY_LENGTH)
+        .collect()
 }
 
-fn matmul(plaintext: &[u8], key: &[Vec<u8>]) -> Vec<u8> {
-    log::trace!(
-        "Matmulling plaintext={:?} with key={:?}",
-        String::from_utf8_lossy(plaintext),
-        key.iter()
-            .cloned()
-            .map(|r| String::from_utf8(r).unwrap())
-            .collect::<Vec<_>>()
-    );
+/// Multiply each `N` element chunk of `plaintext` by `key`, mod `modulus`.
+///
+/// Both the plaintext and the key are indexes into the alphabet.
+fn matmul(plaintext: &[usize], key: &[Vec<usize>], modulus: usize) -> Vec<usize> {
+    log::trace!("Matmulling plaintext={plaintext:?} with key={key:?}");
 
     // our plaintexts are limited to 160 bytes, naive algo is fine
     let mut result = Vec::new();
 
     for i in (0..plaintext.len()).step_by(key.len()) {
         for j in 0..key.len() {
-            let mut s: u32 = 0;
+            let mut s = 0;
             for k in 0..key.len() {
                 log::trace!("    i={i}, j={j}, k={k}, s={s}");
-                s += u32::from(plaintext[i + k] - b'a') * u32::from(key[j][k] - b'a');
+                s += plaintext[i + k] * key[j][k];
             }
-            result.push((s % 26) as u8 + b'a');
-            log::trace!("    Added {}", ((s % 26) as u8 + b'a') as char);
+            result.push(s % modulus);
+            log::trace!("    Added {}", s % modulus);
         }
     }
 
-    log::trace!("    Final Result: {:?}", String::from_utf8(result.clone()));
+    log::trace!("    Final Result: {result:?}");
 
     result
 }
@@ -82,60 +82,182 @@ fn is_perfect_square(n: usize) -> bool {
     }
 }
 
-pub(super) fn hill<R>(plaintext: &str, key: Option<String>, rng: &mut R) -> CipherResult<Cipher>
-where
-    R: Rng + ?Sized,
-{
-    let key = match key {
-        Some(k) => k.bytes().collect(),
-        None => generate_key(rng),
-    };
-
-    log::debug!("Hill: key={:?}", String::from_utf8(key.clone()).unwrap());
+/// Turn `key` into a square matrix of indexes into `alphabet`.
+fn key_matrix(key: &str, alphabet: &Alphabet) -> CipherResult<Vec<Vec<usize>>> {
+    let key_indexes = key
+        .chars()
+        .map(|c| alphabet.index_of(c))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| {
+            CipherError::new(
+                ErrorKind::InvalidKey,
+                "Key must only contain letters of the alphabet".into(),
+            )
+        })?;
 
-    let n = key.len();
+    let n = key_indexes.len();
 
     // key must be a perfect square
     if !is_perfect_square(n) {
         log::debug!("Key length {} is not a perfect square", n);
         return Err(CipherError::new(
-            ErrorKind::KeyError,
+            ErrorKind::InvalidKey,
             "Key length must be a perfect square".into(),
         ));
     }
 
     let side_length = (n as f64).sqrt() as usize;
 
-    // remove non-letters
-    let mut filtered = String::with_capacity(plaintext.len());
-    for c in plaintext.chars() {
-        if c.is_ascii_alphabetic() {
-            filtered.push(c.to_ascii_lowercase());
-        }
-    }
+    // convert 1-d key into square matrix
+    Ok(key_indexes
+        .chunks(side_length)
+        .map(<[usize]>::to_vec)
+        .collect())
+}
 
-    // if the length is not divisible by `side_length`, pad with 'z'
-    let to_pad = filtered.len() % side_length;
+/// The matrix without row `row` and column `col`.
+fn minor(matrix: &[Vec<usize>], row: usize, col: usize) -> Vec<Vec<usize>> {
+    matrix
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != row)
+        .map(|(_, r)| {
+            r.iter()
+                .enumerate()
+                .filter(|&(j, _)| j != col)
+                .map(|(_, &x)| x)
+                .collect()
+        })
+        .collect()
+}
 
-    filtered.push_str(&"z".repeat(to_pad));
+/// The determinant of `matrix`, mod `modulus`, by cofactor expansion along the first row.
+fn determinant(matrix: &[Vec<usize>], modulus: usize) -> usize {
+    if matrix.len() == 1 {
+        return matrix[0][0] % modulus;
+    }
 
-    // convert 1-d key into square matrix
-    let mut bytes = key.clone().into_iter();
-    let mut matrix = vec![vec![0; side_length]; side_length];
-    for row in &mut matrix {
-        for item in row.iter_mut() {
-            *item = bytes.next().unwrap();
+    (0..matrix.len()).fold(0, |det, j| {
+        let term = matrix[0][j] * determinant(&minor(matrix, 0, j), modulus) % modulus;
+        if j % 2 == 0 {
+            (det + term) % modulus
+        } else {
+            (det + modulus - term) % modulus
         }
+    })
+}
+
+/// The inverse of `a` mod `modulus`, if it has one.
+fn mod_inverse(a: usize, modulus: usize) -> Option<usize> {
+    (1..modulus).find(|&x| a * x % modulus == 1)
+}
+
+/// The inverse of `matrix` mod `modulus`, using the adjugate.
+///
+/// Returns a `NonInvertibleKey` error if the determinant shares a factor with `modulus`.
+fn inverse(matrix: &[Vec<usize>], modulus: usize) -> CipherResult<Vec<Vec<usize>>> {
+    let det_inverse = mod_inverse(determinant(matrix, modulus), modulus).ok_or_else(|| {
+        CipherError::new(
+            ErrorKind::NonInvertibleKey,
+            "Key matrix is not invertible, so the ciphertext can't be decrypted".into(),
+        )
+    })?;
+
+    let n = matrix.len();
+    if n == 1 {
+        return Ok(vec![vec![det_inverse]]);
     }
 
-    let r = matmul(filtered.as_bytes(), &matrix);
+    // the adjugate is the transpose of the cofactor matrix
+    Ok((0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| {
+                    let cofactor = determinant(&minor(matrix, j, i), modulus);
+                    let cofactor = if (i + j) % 2 == 0 {
+                        cofactor
+                    } else {
+                        (modulus - cofactor) % modulus
+                    };
+                    cofactor * det_inverse % modulus
+                })
+                .collect()
+        })
+        .collect())
+}
+
+pub(super) fn hill<R>(
+    plaintext: &str,
+    key: Option<String>,
+    alphabet: &Alphabet,
+    rng: &mut R,
+) -> CipherResult<Cipher>
+where
+    R: Rng + ?Sized,
+{
+    let key = match key {
+        Some(k) => k.to_lowercase(),
+        None => generate_key(alphabet, rng),
+    };
+
+    log::debug!("Hill: key={key:?}");
+
+    let matrix = key_matrix(&key, alphabet)?;
+    let side_length = matrix.len();
+
+    // remove non-letters
+    let mut filtered: Vec<_> = plaintext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
 
+    // if the length is not divisible by `side_length`, pad with the last letter
+    let to_pad = (side_length - filtered.len() % side_length) % side_length;
+    filtered.resize(filtered.len() + to_pad, alphabet.len() - 1);
+
+    let r = matmul(&filtered, &matrix, alphabet.len());
+
+    let key = Key {
+        keyword: Some(key),
+        matrix: Some(
+            matrix
+                .iter()
+                .map(|row| row.iter().map(|&i| i as i32).collect())
+                .collect(),
+        ),
+        ..Key::default()
+    };
     Ok(Cipher::new(
-        String::from_utf8(r).unwrap(),
-        Some(String::from_utf8(key).unwrap()),
+        r.into_iter().map(|i| alphabet.symbol(i)).collect(),
+        key,
     ))
 }
 
+/// Decrypt a Hill ciphertext by multiplying by the inverse of the key matrix.
+///
+/// Padding added during encryption is kept, since it can't be told apart from the plaintext.
+pub(super) fn decrypt(ciphertext: &str, key: &str, alphabet: &Alphabet) -> CipherResult<String> {
+    let matrix = key_matrix(&key.to_lowercase(), alphabet)?;
+    let inverse = inverse(&matrix, alphabet.len())?;
+
+    let filtered: Vec<_> = ciphertext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+
+    if filtered.len() % matrix.len() != 0 {
+        return Err(CipherError::new(
+            ErrorKind::InvalidCiphertext,
+            format!("Ciphertext length must be a multiple of {}", matrix.len()),
+        ));
+    }
+
+    Ok(matmul(&filtered, &inverse, alphabet.len())
+        .into_iter()
+        .map(|i| alphabet.symbol(i))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,28 +266,69 @@ mod tests {
     #[test]
     fn test_generate_key() {
         let mut rng = StepRng::new(0, 1);
-        let res = generate_key(&mut rng);
+        let res = generate_key(&Alphabet::english(), &mut rng);
 
-        assert_eq!(res, vec![b'x', b'y', b'z', b'a']);
+        assert_eq!(res, "xyza");
     }
 
     #[test]
     fn test_hill() {
         let mut rng = StepRng::new(0, 1);
-        let res = hill("abcd", Some("abcd".into()), &mut rng).unwrap();
+        let res = hill("abcd", Some("abcd".into()), &Alphabet::english(), &mut rng).unwrap();
 
         assert_eq!(res.ciphertext, "bddn");
     }
 
+    #[test]
+    fn test_hill_padding() {
+        let mut rng = StepRng::new(0, 1);
+        let alphabet = Alphabet::english();
+
+        // "abcd" is padded to "abcdzz"
+        let res = hill("abcd", Some("abcdefghi".into()), &alphabet, &mut rng).unwrap();
+        assert_eq!(res.ciphertext, "foxxad");
+
+        // a multiple of the key size is not padded
+        let res = hill("abcdef", Some("abcdefghi".into()), &alphabet, &mut rng).unwrap();
+        assert_eq!(res.ciphertext, "foxoyi");
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let mut rng = StepRng::new(0, 1);
+        let alphabet = Alphabet::english();
+        let res = hill("attackatdawn", Some("hill".into()), &alphabet, &mut rng).unwrap();
+
+        assert_eq!(
+            decrypt(&res.ciphertext, "hill", &alphabet).unwrap(),
+            "attackatdawn"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_not_invertible() {
+        let err = decrypt("bddn", "abcd", &Alphabet::english()).unwrap_err();
+        assert!(err.to_string().starts_with("NonInvertibleKey"));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let key = vec![vec![6, 24, 1], vec![13, 16, 10], vec![20, 17, 15]];
+
+        assert_eq!(
+            inverse(&key, 26).unwrap(),
+            vec![vec![8, 5, 10], vec![21, 8, 21], vec![21, 12, 8]]
+        );
+    }
+
     #[test]
     fn test_matmul() {
-        let plaintext = b"abcd";
-        let key = vec![vec![b'a', b'b'], vec![b'c', b'd']];
+        let plaintext = [0, 1, 2, 3];
+        let key = vec![vec![0, 1], vec![2, 3]];
 
-        let res = matmul(plaintext, &key);
-        println!("{:?}", String::from_utf8(res.clone()).unwrap());
+        let res = matmul(&plaintext, &key, 26);
 
-        assert_eq!(res, vec![b'b', b'd', b'd', b'n'])
+        assert_eq!(res, vec![1, 3, 3, 13])
     }
 
     #[test]
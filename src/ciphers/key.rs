@@ -0,0 +1,35 @@
+//! Structured keys for every cipher.
+
+use juniper::serde::{Deserialize, Serialize};
+
+/// Everything needed to decrypt a ciphertext, for storing with a cryptogram and printing answer
+/// keys.
+///
+/// Only the fields that apply to the cipher are set.
+#[derive(GraphQLObject, Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(crate = "juniper::serde")]
+pub struct Key {
+    /// The keyword or key phrase.
+    pub keyword: Option<String>,
+    /// The ciphertext letter of each letter of the alphabet, in order, for substitution ciphers.
+    pub mapping: Option<String>,
+    /// How far each letter is shifted, for Caesar and ROT13.
+    pub shift: Option<i32>,
+    /// The key matrix, row by row, for Hill. Letters are numbered from 0.
+    pub matrix: Option<Vec<Vec<i32>>>,
+    /// The letters of the square, row by row, for Polybius squares and tap code.
+    pub square: Option<Vec<String>>,
+    /// The pair of Morse symbols each digit stands for, from 1 to 9, for Morbit. "/" separates
+    /// letters.
+    pub morbit: Option<Vec<String>>,
+}
+
+impl Key {
+    /// A key that is only a keyword.
+    pub(crate) fn from_keyword(keyword: String) -> Self {
+        Self {
+            keyword: Some(keyword),
+            ..Self::default()
+        }
+    }
+}
@@ -3,6 +3,8 @@
 pub(super) mod morbit;
 pub(super) use morbit::morbit;
 
+use super::{CipherError, CipherResult, ErrorKind};
+
 /// Morse alphabet.
 const MORSE_ALPHABET: [&str; 26] = [
     ".-", "-...", "-.-.", "-..", ".", "..-.", "--.", "....", "..", ".---", "-.-", ".-..", "--",
@@ -11,13 +13,16 @@ const MORSE_ALPHABET: [&str; 26] = [
 
 /// Encode an ascii letter in morse code.
 ///
-/// Currently only supports letters.
-fn morse_encode(b: u8) -> String {
+/// Currently only supports letters, and returns an `InvalidPlaintext` error for anything else.
+fn morse_encode(b: u8) -> CipherResult<&'static str> {
     if b.is_ascii_alphabetic() {
         let index = b.to_ascii_lowercase() - b'a';
 
-        MORSE_ALPHABET[index as usize].into()
+        Ok(MORSE_ALPHABET[index as usize])
     } else {
-        panic!("Can only morse encode ascii letters");
+        Err(CipherError::new(
+            ErrorKind::InvalidPlaintext,
+            format!("Can't encode {:?} in Morse code", b as char),
+        ))
     }
 }
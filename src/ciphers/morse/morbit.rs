@@ -1,16 +1,20 @@
 //! Define the morbit cipher.
 
-use super::{super::Cipher, morse_encode};
+use super::{super::Cipher, super::Key, morse_encode};
+use super::{CipherError, CipherResult, ErrorKind};
 use rand::prelude::*;
 use std::collections::HashMap;
 
 static MORBIT_BIGRAMS: &[&str] = &["..", ".-", "./", "-.", "--", "-/", "/.", "/-", "//"];
 
+/// The length of a key, which has a letter for each of [`MORBIT_BIGRAMS`].
+const KEY_LENGTH: usize = 9;
+
 fn generate_key<R>(rng: &mut R) -> String
 where
     R: Rng + ?Sized,
 {
-    (0..9)
+    (0..KEY_LENGTH)
         .map(|_| ((rng.next_u32() as u8) % 26 + b'a') as char)
         .collect()
 }
@@ -20,7 +24,7 @@ fn map_key(v: &Vec<u8>) -> Vec<usize> {
 
     indexes.sort_by_key(|&i| v[i]);
 
-    let mut out = vec![0; 9];
+    let mut out = vec![0; KEY_LENGTH];
     for (i, &v) in indexes.iter().enumerate() {
         out[v] = i;
     }
@@ -33,10 +37,21 @@ fn map_key(v: &Vec<u8>) -> Vec<usize> {
 /// Morbit is an over-encryption of Morse code, similar to Fractionated Morse Code. The plaintext
 /// is first converted into Morse code. Then, pairs of the Morse letters are mapped by the key
 /// to the ciphertext.
-pub(in super::super) fn morbit(s: &str, key: Option<String>) -> Cipher {
+///
+/// The key must be 9 ascii letters. Letters that can't be written in Morse code are left out.
+pub(in super::super) fn morbit<R>(s: &str, key: Option<String>, rng: &mut R) -> CipherResult<Cipher>
+where
+    R: Rng + ?Sized,
+{
     let key = key
-        .unwrap_or_else(|| generate_key(&mut thread_rng()))
+        .unwrap_or_else(|| generate_key(rng))
         .to_ascii_lowercase();
+    if key.len() != KEY_LENGTH || !key.bytes().all(|b| b.is_ascii_lowercase()) {
+        return Err(CipherError::new(
+            ErrorKind::InvalidKey,
+            format!("Key must be {KEY_LENGTH} letters"),
+        ));
+    }
 
     // Step 1: Encode the plaintext in Morse code separating chars with "/" and words with "//"
 
@@ -45,7 +60,7 @@ pub(in super::super) fn morbit(s: &str, key: Option<String>) -> Cipher {
     for word in s.split_ascii_whitespace() {
         for b in word.bytes() {
             if b.is_ascii_alphabetic() {
-                chars.push(morse_encode(b));
+                chars.push(morse_encode(b)?);
             }
         }
         words.push(chars.join("/"));
@@ -59,10 +74,16 @@ pub(in super::super) fn morbit(s: &str, key: Option<String>) -> Cipher {
     let position = map_key(&bytes);
     let mapping: HashMap<_, _> = MORBIT_BIGRAMS
         .iter()
-        .zip(position.into_iter())
+        .zip(position.iter())
         .map(|(&b, i)| (b, (i + 1).to_string()))
         .collect();
 
+    // the digit table is the mapping read the other way, from 1 to 9
+    let mut table = vec![String::new(); MORBIT_BIGRAMS.len()];
+    for (&b, &i) in MORBIT_BIGRAMS.iter().zip(position.iter()) {
+        table[i] = b.to_string();
+    }
+
     let mut out = String::new();
 
     for pair in morse_encoded.chunks(2) {
@@ -71,7 +92,12 @@ pub(in super::super) fn morbit(s: &str, key: Option<String>) -> Cipher {
         out.push_str(&mapping[&format!("{a}{b}") as &str]);
     }
 
-    Cipher::new(out, Some(key))
+    let key = Key {
+        keyword: Some(key),
+        morbit: Some(table),
+        ..Key::default()
+    };
+    Ok(Cipher::new(out, key))
 }
 
 #[cfg(test)]
@@ -95,8 +121,24 @@ mod tests {
 
     #[test]
     fn test_morbit() {
-        let out = morbit("MORE BITS", Some(String::from("MORSECODE")));
+        let out = morbit(
+            "MORE BITS",
+            Some(String::from("MORSECODE")),
+            &mut StepRng::new(0, 1),
+        )
+        .unwrap();
+
+        assert_eq!(out.ciphertext, "32379749578158");
+        assert_eq!(
+            out.key.morbit.unwrap(),
+            vec!["-/", "/-", "--", "//", "..", ".-", "/.", "./", "-."]
+        );
+    }
 
-        assert_eq!(out.unwrap().ciphertext, "32379749578158");
+    #[test]
+    fn test_morbit_bad_key() {
+        let rng = &mut StepRng::new(0, 1);
+        assert!(morbit("MORE BITS", Some("MORSE".into()), rng).is_err());
+        assert!(morbit("MORE BITS", Some("MORSECOD3".into()), rng).is_err());
     }
 }
@@ -0,0 +1,269 @@
+//! Polybius square ciphers.
+//!
+//! [`polybius`], [`tap_code`], [`pigpen`]
+//!
+//! All of these ciphers replace each letter by its position in a grid of letters. The grid is
+//! created by [`SquareBuilder`], which handles keyed alphabets, the merging of letters in 25-letter
+//! squares, and the number of columns.
+
+use super::{Alphabet, Cipher, Key};
+
+/// A grid of letters, read left to right and top to bottom.
+#[derive(Debug)]
+pub(super) struct Square {
+    cells: Vec<char>,
+    columns: usize,
+    alphabet: Alphabet,
+}
+
+impl Square {
+    pub fn builder() -> SquareBuilder {
+        SquareBuilder::default()
+    }
+
+    /// Returns the 0-indexed row and column of `c`, if it is in the square.
+    ///
+    /// Merged letters are looked up by the letter they were merged into.
+    pub fn position(&self, c: char) -> Option<(usize, usize)> {
+        let c = self.alphabet.fold(c);
+
+        self.cells
+            .iter()
+            .position(|&cell| cell == c)
+            .map(|i| (i / self.columns, i % self.columns))
+    }
+
+    /// The letters of each row.
+    pub fn rows(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.columns)
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+}
+
+/// Builder for [`Square`].
+///
+/// By default, the square uses [`Alphabet::latin25`], so it is a 5x5 grid with "j" merged into
+/// "i", and has no keyword.
+#[derive(Debug)]
+pub(super) struct SquareBuilder {
+    alphabet: Alphabet,
+    columns: Option<usize>,
+    keyword: Option<String>,
+}
+
+impl Default for SquareBuilder {
+    fn default() -> Self {
+        Self {
+            alphabet: Alphabet::latin25(),
+            columns: None,
+            keyword: None,
+        }
+    }
+}
+
+impl SquareBuilder {
+    /// Set the alphabet of the square. Merged letters in the alphabet do not appear in the square.
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Set the number of columns in the square.
+    ///
+    /// Defaults to the smallest number of columns that makes the grid square.
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Start the square with the unique letters of `keyword`.
+    pub fn keyword(mut self, keyword: Option<String>) -> Self {
+        self.keyword = keyword;
+        self
+    }
+
+    pub fn build(self) -> Square {
+        let alphabet = self.alphabet;
+        let columns = self.columns.unwrap_or_else(|| {
+            let mut n = 1;
+            while n * n < alphabet.len() {
+                n += 1;
+            }
+            n
+        });
+
+        let mut used = vec![false; alphabet.len()];
+        let mut cells = Vec::with_capacity(alphabet.len());
+
+        let keyword = self.keyword.unwrap_or_default();
+        for i in keyword
+            .chars()
+            .filter_map(|c| alphabet.index_of(c))
+            .chain(0..alphabet.len())
+        {
+            if !used[i] {
+                cells.push(alphabet.symbol(i));
+                used[i] = true;
+            }
+        }
+
+        Square {
+            cells,
+            columns,
+            alphabet,
+        }
+    }
+}
+
+/// Encode each word of `s` letter by letter with `encode_letter`.
+///
+/// Letters are separated by `letter_sep` and words are separated by " / ". Non-letters are dropped.
+fn encode_words<F>(s: &str, letter_sep: &str, mut encode_letter: F) -> String
+where
+    F: FnMut(char) -> Option<String>,
+{
+    s.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter_map(&mut encode_letter)
+                .collect::<Vec<_>>()
+                .join(letter_sep)
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Polybius Square
+///
+/// Each letter is replaced by its 1-indexed row and column in a square of `alphabet`, which is
+/// usually [`Alphabet::latin25`]. If `key` is given, the square starts with the letters of the key.
+pub(super) fn polybius(s: &str, key: Option<String>, alphabet: &Alphabet) -> Cipher {
+    let square = Square::builder()
+        .alphabet(alphabet.clone())
+        .keyword(key.clone())
+        .build();
+
+    let out = encode_words(s, " ", |c| {
+        square
+            .position(c)
+            .map(|(row, col)| format!("{}{}", row + 1, col + 1))
+    });
+
+    let key = Key {
+        keyword: key.map(|k| k.to_lowercase()),
+        square: Some(square.rows()),
+        ..Key::default()
+    };
+    Cipher::new(out, key)
+}
+
+/// Tap Code
+///
+/// Tap code uses an unkeyed square of `alphabet`, which is usually the English alphabet with "k"
+/// merged into "c". Each letter is written as two groups of dots, the first being the row and the
+/// second the column.
+pub(super) fn tap_code(s: &str, alphabet: &Alphabet) -> Cipher {
+    let square = Square::builder().alphabet(alphabet.clone()).build();
+
+    let out = encode_words(s, "  ", |c| {
+        square
+            .position(c)
+            .map(|(row, col)| format!("{} {}", ".".repeat(row + 1), ".".repeat(col + 1)))
+    });
+
+    let key = Key {
+        square: Some(square.rows()),
+        ..Key::default()
+    };
+    Cipher::new(out, key)
+}
+
+/// Pigpen symbol identifiers, in the order the letters appear in the pigpen grids.
+///
+/// "G" is the tic-tac-toe grid and "X" is the X shaped grid. A "D" means the symbol has a dot.
+/// The number is the cell of the grid, read left to right and top to bottom.
+const PIGPEN_SYMBOLS: [&str; 26] = [
+    "G1", "G2", "G3", "G4", "G5", "G6", "G7", "G8", "G9", "GD1", "GD2", "GD3", "GD4", "GD5", "GD6",
+    "GD7", "GD8", "GD9", "X1", "X2", "X3", "X4", "XD1", "XD2", "XD3", "XD4",
+];
+
+/// Pigpen Cipher
+///
+/// Each letter is replaced by the identifier of its pigpen symbol. See [`PIGPEN_SYMBOLS`] for
+/// how the identifiers are named. The English alphabet is laid out in rows of 9, so the row and
+/// column of a letter gives the index of its symbol.
+pub(super) fn pigpen(s: &str) -> Cipher {
+    let square = Square::builder()
+        .alphabet(Alphabet::english())
+        .columns(9)
+        .build();
+
+    let out = encode_words(s, " ", |c| {
+        square
+            .position(c)
+            .map(|(row, col)| PIGPEN_SYMBOLS[row * 9 + col].to_string())
+    });
+
+    // the pigpen grids are always the same, so there is no key
+    Cipher::new(out, Key::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_default() {
+        let square = Square::builder().build();
+
+        assert_eq!(square.position('a'), Some((0, 0)));
+        assert_eq!(square.position('i'), Some((1, 3)));
+        assert_eq!(square.position('J'), Some((1, 3)));
+        assert_eq!(square.position('z'), Some((4, 4)));
+        assert_eq!(square.position('!'), None);
+    }
+
+    #[test]
+    fn test_square_keyword() {
+        let square = Square::builder().keyword(Some("Jumble".into())).build();
+
+        assert_eq!(
+            square.cells.iter().collect::<String>(),
+            "iumbleacdfghknopqrstvwxyz"
+        );
+    }
+
+    #[test]
+    fn test_square_alphanumeric() {
+        let square = Square::builder()
+            .alphabet(Alphabet::new("abcdefghijklmnopqrstuvwxyz0123456789"))
+            .build();
+
+        assert_eq!(square.position('9'), Some((5, 5)));
+    }
+
+    #[test]
+    fn test_polybius() {
+        let res = polybius("Hi there!", None, &Alphabet::latin25());
+
+        assert_eq!(res.ciphertext, "23 24 / 44 23 15 42 15");
+        assert_eq!(res.key.square.unwrap()[0], "abcde");
+    }
+
+    #[test]
+    fn test_tap_code() {
+        let res = tap_code("Kit", &Alphabet::english().with_merge('k', 'c'));
+
+        assert_eq!(res.ciphertext, ". ...  .. ....  .... ....");
+    }
+
+    #[test]
+    fn test_pigpen() {
+        let res = pigpen("jaws z");
+
+        assert_eq!(res.ciphertext, "GD1 G1 XD1 X1 / XD4");
+    }
+}
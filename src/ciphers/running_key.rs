@@ -0,0 +1,158 @@
+//! Running key cipher.
+//!
+//! A running key cipher is a Vigenère cipher where the key is a long piece of text instead of a
+//! short repeated word. Here, the key is another quote from the quote corpus.
+
+use super::{Alphabet, Cipher, Key};
+use super::{CipherError, CipherResult, ErrorKind};
+use rand::prelude::*;
+
+fn letter_count(s: &str, alphabet: &Alphabet) -> usize {
+    s.chars()
+        .filter(|&c| alphabet.index_of(c).is_some())
+        .count()
+}
+
+/// Running Key Cipher
+///
+/// Each letter of the plaintext is shifted by the index in `alphabet` of the corresponding letter of
+/// the key, so "a" is a shift of 0. Non-letters are kept and do not use up a letter of the key.
+///
+/// If `key` is not given, a random quote from `quotes` with at least as many letters as the
+/// plaintext is used. The returned key is the full text of the quote, so the answer identifies
+/// which quote was used.
+pub(super) fn running_key<R>(
+    s: &str,
+    key: Option<String>,
+    quotes: &[&str],
+    alphabet: &Alphabet,
+    rng: &mut R,
+) -> CipherResult<Cipher>
+where
+    R: Rng + ?Sized,
+{
+    let needed = letter_count(s, alphabet);
+
+    let key = match key {
+        Some(k) => k,
+        None => {
+            let candidates: Vec<_> = quotes
+                .iter()
+                .filter(|&&q| q != s && letter_count(q, alphabet) >= needed)
+                .collect();
+
+            match candidates.choose(rng) {
+                Some(&&q) => q.to_string(),
+                None => {
+                    return Err(CipherError::new(
+                        ErrorKind::NoQuoteAvailable,
+                        "No quote is long enough to use as a running key".into(),
+                    ))
+                }
+            }
+        }
+    };
+
+    if letter_count(&key, alphabet) < needed {
+        return Err(CipherError::new(
+            ErrorKind::InvalidKey,
+            format!("Key must have at least {needed} letters"),
+        ));
+    }
+
+    let mut key_stream = key.chars().filter_map(|c| alphabet.index_of(c));
+
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match alphabet.index_of(c) {
+            // unwrap is safe because the key has at least as many letters as the plaintext
+            Some(_) => out.push(alphabet.shift(c, key_stream.next().unwrap()).unwrap()),
+            None => out.push(c),
+        }
+    }
+
+    Ok(Cipher::new(out, Key::from_keyword(key)))
+}
+
+/// Decrypt a running key cipher by shifting each letter back by the key.
+pub(super) fn decrypt(s: &str, key: &str, alphabet: &Alphabet) -> CipherResult<String> {
+    if letter_count(key, alphabet) < letter_count(s, alphabet) {
+        return Err(CipherError::new(
+            ErrorKind::InvalidKey,
+            "Key must have at least as many letters as the ciphertext".into(),
+        ));
+    }
+
+    let mut key_stream = key.chars().filter_map(|c| alphabet.index_of(c));
+
+    Ok(s.chars()
+        .map(|c| match alphabet.index_of(c) {
+            Some(_) => {
+                let by = alphabet.len() - key_stream.next().unwrap();
+                alphabet.shift(c, by % alphabet.len()).unwrap()
+            }
+            None => c,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    #[test]
+    fn test_running_key() {
+        let res = running_key(
+            "Attack at dawn!",
+            Some("Lorem ipsum dolor sit amet".into()),
+            &[],
+            &Alphabet::english(),
+            &mut StepRng::new(0, 1),
+        )
+        .unwrap();
+
+        assert_eq!(res.ciphertext, "Lhkeos pl xmzb!");
+        assert_eq!(res.key.keyword, Some("Lorem ipsum dolor sit amet".into()));
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let res = decrypt(
+            "Lhkeos pl xmzb!",
+            "Lorem ipsum dolor sit amet",
+            &Alphabet::english(),
+        )
+        .unwrap();
+
+        assert_eq!(res, "Attack at dawn!");
+    }
+
+    #[test]
+    fn test_running_key_from_quotes() {
+        let quotes = ["short", "attack at dawn", "this quote is long enough"];
+        let res = running_key(
+            "attack at dawn",
+            None,
+            &quotes,
+            &Alphabet::english(),
+            &mut StepRng::new(0, 1),
+        )
+        .unwrap();
+
+        assert_eq!(res.key.keyword, Some("this quote is long enough".into()));
+    }
+
+    #[test]
+    fn test_running_key_too_short() {
+        let res = running_key(
+            "attack at dawn",
+            Some("abc".into()),
+            &[],
+            &Alphabet::english(),
+            &mut StepRng::new(0, 1),
+        );
+
+        assert!(res.is_err());
+    }
+}
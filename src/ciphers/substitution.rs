@@ -1,109 +1,202 @@
 //! Definition of subtitution ciphers.
 //!
 //! [`rot13`], [`caeser`], [`aristocrat`], [`patristocrat`]
+//!
+//! Every substitution is decrypted by [`decrypt`] with the inverse of its mapping.
 
-use super::{match_case, shift_letter, Cipher, ALPHABET, WORDS};
-use itertools::Itertools;
+use super::{Alphabet, Cipher, CipherError, CipherResult, ErrorKind, Key, WORDS};
 use rand::prelude::*;
 
-const ROT13_MAPPING: [u8; 26] = *b"nopqrstuvwxyzabcdefghijklm";
-
 /// Generic function that implements the various substitution ciphers
 ///
-/// `mapping` maps letters from plaintext and ciphertext. For example, a b'e' in the 0th index
-/// would mean that b'a' maps to b'e'.
-fn substitute(s: &str, mapping: &[u8], keep_whitespace: bool) -> String {
+/// `mapping` maps letters from plaintext and ciphertext by their index in `alphabet`. For example,
+/// an 'e' in the 0th index would mean that 'a' maps to 'e'. If `keep_whitespace` is false,
+/// whitespace is removed. Grouping the remaining characters is left to
+/// [`crate::format::Format`].
+fn substitute(s: &str, alphabet: &Alphabet, mapping: &[char], keep_whitespace: bool) -> String {
     let mut out = String::with_capacity(s.len());
-    for b in s.bytes() {
-        if !keep_whitespace && b.is_ascii_whitespace() {
+    for c in s.chars() {
+        if !keep_whitespace && c.is_whitespace() {
             continue;
         }
 
-        if b.is_ascii_alphabetic() {
-            out.push(match_case(mapping[(b.to_ascii_uppercase() - b'A') as usize], b) as char);
-        } else {
-            out.push(b as char);
+        match alphabet.index_of(c) {
+            Some(i) => out.push(Alphabet::match_case(mapping[i], c)),
+            None => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Returns true if no letter maps to itself.
+fn is_derangement(alphabet: &Alphabet, mapping: &[char]) -> bool {
+    mapping.iter().zip(alphabet.symbols()).all(|p| p.0 != p.1)
+}
+
+/// Randomly map each letter of `alphabet` to a different letter.
+fn random_mapping<R>(alphabet: &Alphabet, rng: &mut R) -> Vec<char>
+where
+    R: Rng + ?Sized,
+{
+    let mut mapping: Vec<_> = alphabet
+        .symbols()
+        .choose_multiple(rng, alphabet.len())
+        .copied()
+        .collect();
+    loop {
+        if is_derangement(alphabet, &mapping) {
+            break;
+        }
+        mapping.shuffle(rng);
+    }
+
+    mapping
+}
+
+/// The key of a substitution with `mapping`.
+fn mapping_key(mapping: &[char]) -> Key {
+    Key {
+        mapping: Some(mapping.iter().collect()),
+        ..Key::default()
+    }
+}
+
+/// Create an alphabet starting with the unique letters of `key`, followed by the remaining letters
+/// in order.
+fn keyed_alphabet(key: &str, alphabet: &Alphabet) -> Vec<char> {
+    let mut used = vec![false; alphabet.len()];
+
+    let mut mapping = Vec::with_capacity(alphabet.len());
+    for i in key
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .chain(0..alphabet.len())
+    {
+        // letters can only be used once
+        if !used[i] {
+            mapping.push(alphabet.symbol(i));
+            used[i] = true;
         }
     }
 
-    if keep_whitespace {
-        out
-    } else {
-        let mut interspersed = String::with_capacity(s.len() + s.len() / 5);
-        for chunk in &out.chars().chunks(5) {
-            chunk.for_each(|c| interspersed.push(c));
-            interspersed.push(' ');
+    mapping
+}
+
+/// The mapping of a Caesar cipher that shifts by `shift`.
+fn shift_mapping(shift: usize, alphabet: &Alphabet) -> Vec<char> {
+    let n = alphabet.len();
+    (0..n).map(|i| alphabet.symbol((i + shift) % n)).collect()
+}
+
+/// The mapping of a K1 patristocrat with `key`.
+fn k1_mapping(key: &str, alphabet: &Alphabet) -> Vec<char> {
+    // the letters not in the key are appended in alphabetical order
+    let mut mapping = keyed_alphabet(key, alphabet);
+
+    // finally, shift the plaintext until no letter maps to itself
+    // note: we don't shuffle like in `patristocrat` because we must keep the key in place
+    loop {
+        if is_derangement(alphabet, &mapping) {
+            break;
         }
-        interspersed.pop();
+        mapping.rotate_right(1);
+    }
 
-        interspersed
+    mapping
+}
+
+/// The mapping of a K2 patristocrat with `key`.
+fn k2_mapping(key: &str, alphabet: &Alphabet) -> Vec<char> {
+    // first, map the first `key.len()` plaintext chars to the key, then map the rest of the
+    // plaintext to the rest of the ciphertext alphabet
+    let mut mapping = keyed_alphabet(key, alphabet);
+
+    // finally, shift the mapping until no letter maps to itself
+    // note: we don't shuffle like in patristocrat because we must keep the key in place
+    loop {
+        if is_derangement(alphabet, &mapping) {
+            break;
+        }
+        mapping.rotate_left(1);
     }
+
+    mapping
 }
 
 /// Shift each letter by 13.
 ///
-/// The cipher shifts each letter by 13. It is essentially a Caeser cipher but with a fixed shift.
-pub(super) fn rot13(s: &str) -> Cipher {
-    Cipher::new(substitute(s, &ROT13_MAPPING, true), None)
+/// The cipher shifts each letter by half the length of the alphabet, which is 13 for English. It
+/// is essentially a Caeser cipher but with a fixed shift.
+pub(super) fn rot13(s: &str, alphabet: &Alphabet) -> Cipher {
+    let n = alphabet.len();
+    let mapping = shift_mapping(n / 2, alphabet);
+
+    let key = Key {
+        shift: Some((n / 2) as i32),
+        ..mapping_key(&mapping)
+    };
+    Cipher::new(substitute(s, alphabet, &mapping, true), key)
 }
 
 /// Randomly choose a shift `s` and shift each letter by `s`.
-pub(super) fn caeser<R>(s: &str, rng: &mut R) -> Cipher
+pub(super) fn caeser<R>(s: &str, alphabet: &Alphabet, rng: &mut R) -> Cipher
 where
     R: Rng + ?Sized,
 {
+    let n = alphabet.len();
     let shift = loop {
-        let x = rng.next_u32();
+        let x = rng.next_u32() as usize % n;
         if x != 0 {
             break x;
         }
-    } as u8;
+    };
 
-    let mut mapping = [0u8; 255];
-    for b in ALPHABET {
-        mapping[(b - b'a') as usize] = shift_letter(b, shift);
-    }
+    let mapping = shift_mapping(shift, alphabet);
 
-    Cipher::new(substitute(s, &mapping, true), None)
+    let key = Key {
+        shift: Some(shift as i32),
+        ..mapping_key(&mapping)
+    };
+    Cipher::new(substitute(s, alphabet, &mapping, true), key)
 }
 
 /// Monoalphabetic substitution cipher.
 ///
 /// The cipher uniquely maps each letter in the alphabet to a different letter in the alphabet.
 /// This mapping is then used to map the input string to the output string.
-pub(super) fn aristocrat<R>(s: &str, rng: &mut R) -> Cipher
+pub(super) fn aristocrat<R>(s: &str, alphabet: &Alphabet, rng: &mut R) -> Cipher
 where
     R: Rng + ?Sized,
 {
-    let mut mapping: Vec<_> = ALPHABET.choose_multiple(rng, 26).copied().collect();
-    loop {
-        if (mapping.iter().zip(ALPHABET.iter())).all(|p| p.0 != p.1) {
-            break;
-        }
-        mapping.shuffle(rng);
-    }
+    let mapping = random_mapping(alphabet, rng);
 
-    Cipher::new(substitute(s, &mapping, true), None)
+    Cipher::new(
+        substitute(s, alphabet, &mapping, true),
+        mapping_key(&mapping),
+    )
 }
 
 /// Similar to aristocrat, but removes all spaces.
-pub(super) fn patristocrat<R>(s: &str, rng: &mut R) -> Cipher
+pub(super) fn patristocrat<R>(s: &str, alphabet: &Alphabet, rng: &mut R) -> Cipher
 where
     R: Rng + ?Sized,
 {
-    let mut mapping = ALPHABET.choose_multiple(rng, 26).copied().collect_vec();
-    loop {
-        if (mapping.iter().zip(ALPHABET.iter())).all(|p| p.0 != p.1) {
-            break;
-        }
-        mapping.shuffle(rng);
-    }
+    let mapping = random_mapping(alphabet, rng);
 
-    Cipher::new(substitute(s, &mapping, false), None)
+    Cipher::new(
+        substitute(s, alphabet, &mapping, false),
+        mapping_key(&mapping),
+    )
 }
 
 /// Essentially the same as patristocrat, but uses a keyed plaintext.
-pub(super) fn patristocrat_k1<R>(s: &str, key: Option<String>, rng: &mut R) -> Cipher
+pub(super) fn patristocrat_k1<R>(
+    s: &str,
+    key: Option<String>,
+    alphabet: &Alphabet,
+    rng: &mut R,
+) -> Cipher
 where
     R: Rng + ?Sized,
 {
@@ -112,10 +205,6 @@ where
     // samplekybcdfghijnoqrtuvwxz
     // ABCDEFGHIJKLMNOPQRSTUVWXYZ
     // In this case, our key is "samplekey".
-    let mut key_chars = [false; 255];
-
-    let mut mapping = [0u8; 26];
-    let mut i = 0;
 
     // mapping starts with the key, which is either randomly chosen or given
     // unwrap is safe because WORDS is guaranteed non-empty
@@ -124,38 +213,22 @@ where
         .unwrap_or_else(|| WORDS.choose(rng).unwrap())
         .to_lowercase();
 
-    for b in key.bytes() {
-        // letters can only be used once
-        if key_chars[b as usize] {
-            continue;
-        }
-        mapping[i] = b;
-        key_chars[b as usize] = true;
-        i += 1;
-    }
-
-    // next, the letters not in the key are appended in alphabetical order
-    for b in ALPHABET {
-        if !key_chars[b as usize] {
-            mapping[i] = b;
-            i += 1;
-        }
-    }
-
-    // finally, shift the plaintext until no letter maps to itself
-    // note: we don't shuffle like in `patristocrat` because we must keep the key in place
-    loop {
-        if (mapping.iter().zip(ALPHABET.iter())).all(|p| p.0 != p.1) {
-            break;
-        }
-        mapping.rotate_right(1);
-    }
+    let mapping = k1_mapping(&key, alphabet);
 
-    Cipher::new(substitute(s, &mapping, false), Some(key))
+    let key = Key {
+        keyword: Some(key),
+        ..mapping_key(&mapping)
+    };
+    Cipher::new(substitute(s, alphabet, &mapping, false), key)
 }
 
 /// Essentially the same as patristocrat, but uses a keyed ciphertext.
-pub(super) fn patristocrat_k2<R>(s: &str, key: Option<String>, rng: &mut R) -> Cipher
+pub(super) fn patristocrat_k2<R>(
+    s: &str,
+    key: Option<String>,
+    alphabet: &Alphabet,
+    rng: &mut R,
+) -> Cipher
 where
     R: Rng + ?Sized,
 {
@@ -165,9 +238,6 @@ where
     // SAMPLEKYBCDFGHIJNOQRTUVWXZ
     // we would then shift the ciphertext until no letters map to themselves. This is almost
     // identical to k1 patristocrat.
-    let mut key_chars = [false; 255];
-
-    let mut mapping = [0u8; 26];
 
     // unwrap is safe because WORDS is guaranteed non-empty
     let key = key
@@ -175,49 +245,72 @@ where
         .unwrap_or_else(|| WORDS.choose(rng).unwrap())
         .to_lowercase();
 
-    let mut i = 0;
+    let mapping = k2_mapping(&key, alphabet);
 
-    // first, map the first `key.len()` plaintext chars to the key.
-    for b in key.bytes() {
-        if key_chars[b as usize] {
-            continue;
-        }
-        mapping[i] = b;
-        key_chars[b as usize] = true;
-        i += 1;
-    }
+    let key = Key {
+        keyword: Some(key),
+        ..mapping_key(&mapping)
+    };
+    Cipher::new(substitute(s, alphabet, &mapping, false), key)
+}
 
-    // next, we map the rest of the plaintext to the rest of the ciphertext alphabet
-    for b in ALPHABET {
-        if !key_chars[b as usize] {
-            mapping[i] = b;
-            i += 1;
-        }
-    }
+/// The key of a substitution, for [`decrypt`].
+pub(super) enum SubstitutionKey<'a> {
+    /// Caesar and ROT13 shifts.
+    Shift(usize),
+    /// The ciphertext letter of each letter of the alphabet, as in [`Key::mapping`].
+    Mapping(&'a str),
+    /// The keyword of a K1 patristocrat.
+    K1(&'a str),
+    /// The keyword of a K2 patristocrat.
+    K2(&'a str),
+}
 
-    // finally, shift the mapping until no letter maps to itself
-    // note: we don't shuffle like in patristocrat because we must keep the key in place
-    loop {
-        if (mapping.iter().zip(ALPHABET.iter())).all(|p| p.0 != p.1) {
-            break;
+/// Decrypt any substitution cipher in this module.
+///
+/// Patristocrats lose their spaces when encrypted, so they are decrypted without spaces.
+pub(super) fn decrypt(s: &str, key: SubstitutionKey, alphabet: &Alphabet) -> CipherResult<String> {
+    let mapping = match key {
+        SubstitutionKey::Shift(shift) => shift_mapping(shift % alphabet.len(), alphabet),
+        SubstitutionKey::Mapping(m) => m.chars().map(|c| alphabet.fold(c)).collect(),
+        SubstitutionKey::K1(k) => k1_mapping(&k.to_lowercase(), alphabet),
+        SubstitutionKey::K2(k) => k2_mapping(&k.to_lowercase(), alphabet),
+    };
+
+    // the inverse maps each ciphertext letter back to its plaintext letter
+    let mut inverse = vec![None; alphabet.len()];
+    for (i, &c) in mapping.iter().enumerate() {
+        if let Some(j) = alphabet.index_of(c) {
+            inverse[j] = Some(alphabet.symbol(i));
         }
-        mapping.rotate_left(1);
     }
 
-    Cipher::new(substitute(s, &mapping, false), Some(key))
+    let inverse = inverse
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .filter(|_| mapping.len() == alphabet.len())
+        .ok_or_else(|| {
+            CipherError::new(
+                ErrorKind::InvalidKey,
+                "Mapping must contain every letter of the alphabet once".into(),
+            )
+        })?;
+
+    Ok(substitute(s, alphabet, &inverse, true))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
 
     static TEST_TEXT: &str =
         "abcdefghijklmnopqrstuvwxyz 0123456789-!'\".ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
     #[test]
     fn test_rot13() {
-        let res = rot13(TEST_TEXT);
+        let res = rot13(TEST_TEXT, &Alphabet::english());
 
         assert_eq!(
             res.ciphertext,
@@ -227,22 +320,26 @@ mod tests {
 
     #[test]
     fn test_caesar() {
-        let res = caeser(TEST_TEXT, &mut StepRng::new(0, 1));
+        let res = caeser(TEST_TEXT, &Alphabet::english(), &mut StepRng::new(0, 1));
         let ans = "bcdefghijklmnopqrstuvwxyza 0123456789-!'\".BCDEFGHIJKLMNOPQRSTUVWXYZA";
         assert_eq!(res.ciphertext, ans);
+        assert_eq!(res.key.shift, Some(1));
+        assert_eq!(
+            res.key.mapping.as_deref(),
+            Some("bcdefghijklmnopqrstuvwxyza")
+        );
     }
 
     #[test]
     fn test_aristocrat() {
-        let res = aristocrat(TEST_TEXT, &mut StepRng::new(0, 1));
+        let res = aristocrat(TEST_TEXT, &Alphabet::english(), &mut StepRng::new(0, 1));
         let ans = "bcdefghijklmnopqrstuvwxyza 0123456789-!'\".BCDEFGHIJKLMNOPQRSTUVWXYZA";
         assert_eq!(res.ciphertext, ans);
     }
     #[test]
     fn test_patristocrat() {
-        let res = patristocrat(TEST_TEXT, &mut StepRng::new(0, 1));
-        let ans =
-            "bcdef ghijk lmnop qrstu vwxyz a0123 45678 9-!'\" .BCDE FGHIJ KLMNO PQRST UVWXY ZA";
+        let res = patristocrat(TEST_TEXT, &Alphabet::english(), &mut StepRng::new(0, 1));
+        let ans = "bcdefghijklmnopqrstuvwxyza0123456789-!'\".BCDEFGHIJKLMNOPQRSTUVWXYZA";
         assert_eq!(res.ciphertext, ans);
     }
 
@@ -251,9 +348,10 @@ mod tests {
         let res = patristocrat_k1(
             "bcdefghijklmnopqrstuvwxyza",
             Some(String::from("testkey")),
+            &Alphabet::english(),
             &mut StepRng::new(0, 1),
         );
-        let ans = "tesky abcdf ghijl mnopq ruvwx z";
+        let ans = "teskyabcdfghijlmnopqruvwxz";
 
         assert_eq!(res.ciphertext, ans);
     }
@@ -263,10 +361,66 @@ mod tests {
         let res = patristocrat_k2(
             "yzabcdefghijklmnopqrstuvwx",
             Some(String::from("testkey")),
+            &Alphabet::english(),
             &mut StepRng::new(0, 1),
         );
-        let ans = "tesky abcdf ghijl mnopq ruvwx z";
+        let ans = "teskyabcdfghijlmnopqruvwxz";
 
         assert_eq!(res.ciphertext, ans);
     }
+
+    #[test]
+    fn test_decrypt() {
+        let alphabet = Alphabet::english();
+        let plaintext = "Attack at dawn!";
+        let rng = &mut StdRng::seed_from_u64(0);
+
+        let res = caeser(plaintext, &alphabet, rng);
+        let shift = res.key.shift.unwrap() as usize;
+        assert_eq!(
+            decrypt(&res.ciphertext, SubstitutionKey::Shift(shift), &alphabet).unwrap(),
+            plaintext
+        );
+
+        let res = aristocrat(plaintext, &alphabet, rng);
+        let mapping = res.key.mapping.unwrap();
+        assert_eq!(
+            decrypt(
+                &res.ciphertext,
+                SubstitutionKey::Mapping(&mapping),
+                &alphabet
+            )
+            .unwrap(),
+            plaintext
+        );
+
+        let res = patristocrat_k2(plaintext, Some("Jumble".into()), &alphabet, rng);
+        assert_eq!(
+            decrypt(&res.ciphertext, SubstitutionKey::K2("JUMBLE"), &alphabet).unwrap(),
+            "Attackatdawn!"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_bad_mapping() {
+        let res = decrypt("abc", SubstitutionKey::Mapping("aab"), &Alphabet::english());
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_substitute_multibyte() {
+        let mapping: Vec<_> = ('b'..='z').chain(Some('a')).collect();
+        let res = substitute("“It’s — Zoë”", &Alphabet::english(), &mapping, true);
+
+        assert_eq!(res, "“Ju’t — Apë”");
+    }
+
+    #[test]
+    fn test_substitute_merged_alphabet() {
+        let alphabet = Alphabet::latin25();
+        let mapping: Vec<_> = alphabet.symbols().iter().rev().copied().collect();
+
+        assert_eq!(substitute("Jig", &alphabet, &mapping, true), "Rrt");
+    }
 }
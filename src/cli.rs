@@ -0,0 +1,455 @@
+//! This module implements the command line subcommands.
+//!
+//! Only `export` and `migrate` need the database. The others call the ciphers directly, and read
+//! quotes from `QUOTES_FILE` instead of the store, so they work without a server:
+//!
+//! - `encrypt` encrypts a given text,
+//! - `decrypt` decrypts a given ciphertext with its key,
+//! - `random` encrypts a random quote,
+//! - `test` generates a whole test from a JSON file of [`QuestionSpec`]s,
+//! - `migrate up` applies pending schema migrations, and `migrate status` lists them.
+//!
+//! Every subcommand writes plain text by default, or JSON with `--format json`.
+
+use super::ciphers::Cipher;
+use super::config;
+use super::cryptogram::{self, Cryptogram, Length, Type};
+use super::export::{ExportFormat, ExportSource};
+use super::quotes::{self, Quote};
+use super::store;
+use super::test::{self, Question, QuestionSpec};
+use juniper::serde::de::DeserializeOwned;
+use rand::{thread_rng, Rng};
+use serde_json::json;
+use std::error::Error;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage:
+    cryptograms encrypt --type TYPE --text TEXT [--key KEY] [--seed SEED] [--format text|json]
+    cryptograms decrypt --type TYPE --text TEXT [--key KEY] [--format text|json]
+    cryptograms random [--type TYPE] [--length short|medium|long] [--seed SEED] [--format text|json]
+    cryptograms test --spec FILE [--seed SEED] [--format text|json]
+    cryptograms export <html|latex> (--test ID | --tokens TOKEN,...) [--answer-key] [--output FILE]
+    cryptograms migrate <up|status> [--format text|json]
+
+TYPE is a cipher type in kebab case, such as aristocrat or patristocrat-k2.";
+
+/// How a subcommand writes its result.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Output {
+    Text,
+    Json,
+}
+
+/// Options shared by the subcommands. Each subcommand only accepts some of them.
+#[derive(Debug)]
+struct Args {
+    r#type: Option<Type>,
+    length: Option<Length>,
+    key: Option<String>,
+    text: Option<String>,
+    seed: Option<i32>,
+    spec: Option<String>,
+    output: Output,
+}
+
+/// Parse a variant name like `patristocrat-k2` into `T`, which is deserialized from `PatristocratK2`.
+fn parse_variant<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    let name: String = s
+        .split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .concat();
+
+    serde_json::from_value(serde_json::Value::String(name))
+        .map_err(|_| format!("unknown value {s:?}"))
+}
+
+/// Parse `args`, allowing only the options in `allowed`.
+fn parse(args: &[String], allowed: &[&str]) -> Result<Args, String> {
+    let mut parsed = Args {
+        r#type: None,
+        length: None,
+        key: None,
+        text: None,
+        seed: None,
+        spec: None,
+        output: Output::Text,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !allowed.contains(&arg.as_str()) {
+            return Err(format!("unknown argument {arg:?}"));
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {arg}"))?;
+        match arg.as_str() {
+            "--type" => parsed.r#type = Some(parse_variant(value)?),
+            "--length" => parsed.length = Some(parse_variant(value)?),
+            "--key" => parsed.key = Some(value.clone()),
+            "--text" => parsed.text = Some(value.clone()),
+            "--seed" => {
+                parsed.seed = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid seed {value:?}"))?,
+                )
+            }
+            "--spec" => parsed.spec = Some(value.clone()),
+            "--format" => {
+                parsed.output = match value.as_str() {
+                    "text" => Output::Text,
+                    "json" => Output::Json,
+                    _ => return Err(format!("unknown format {value:?}")),
+                }
+            }
+            _ => unreachable!("every allowed argument is handled"),
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// The fields of `cryptogram` that are printed, including the answer.
+fn cryptogram_json(cryptogram: &Cryptogram) -> serde_json::Value {
+    json!({
+        "type": cryptogram.r#type,
+        "length": cryptogram.length,
+        "author": cryptogram.author,
+        "seed": cryptogram.seed,
+        "ciphertext": cryptogram.ciphertext,
+        "plaintext": cryptogram.plaintext,
+        "key": cryptogram.key,
+        "frequencies": cryptogram.frequencies,
+        "difficulty": cryptogram.difficulty,
+    })
+}
+
+fn cryptogram_text(cryptogram: &Cryptogram) -> String {
+    let mut lines = vec![
+        format!("Type: {}", cryptogram.r#type),
+        format!("Seed: {}", cryptogram.seed),
+        format!("Ciphertext: {}", cryptogram.ciphertext),
+        format!("Plaintext: {}", cryptogram.plaintext),
+    ];
+    if let Some(author) = &cryptogram.author {
+        lines.push(format!("Author: {author}"));
+    }
+    if let Some(keyword) = &cryptogram.key.keyword {
+        lines.push(format!("Key: {keyword}"));
+    }
+    if let Some(mapping) = &cryptogram.key.mapping {
+        lines.push(format!("Mapping: {}", mapping.to_uppercase()));
+    }
+    if let Some(difficulty) = cryptogram.difficulty {
+        lines.push(format!("Difficulty: {difficulty:.2}"));
+    }
+
+    lines.join("\n")
+}
+
+fn write_cryptogram(cryptogram: &Cryptogram, output: Output) -> String {
+    match output {
+        Output::Text => cryptogram_text(cryptogram),
+        Output::Json => cryptogram_json(cryptogram).to_string(),
+    }
+}
+
+/// The quotes in `QUOTES_FILE`.
+///
+/// If the file is not set there are no quotes, which is an error if they are `required`.
+fn read_quotes(required: bool) -> Result<Vec<Quote>, Box<dyn Error>> {
+    match &config::get().quotes_file {
+        Some(path) => quotes::from_file(path),
+        None if required => Err("QUOTES_FILE must be set".into()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn encrypt(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let args = parse(args, &["--type", "--key", "--text", "--seed", "--format"])?;
+    let r#type = args.r#type.ok_or("--type is required")?;
+    let quotes = read_quotes(matches!(r#type, Type::Book | Type::RunningKey))?;
+
+    let cryptogram = Cryptogram::new(cryptogram::Options {
+        quotes: &quotes,
+        plaintext: Some(args.text.ok_or("--text is required")?),
+        r#type: Some(r#type),
+        key: args.key,
+        seed: args.seed,
+        rate: true,
+        ..cryptogram::Options::default()
+    })?;
+
+    Ok(write_cryptogram(&cryptogram, args.output))
+}
+
+fn decrypt(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let args = parse(args, &["--type", "--key", "--text", "--format"])?;
+    let r#type = args.r#type.ok_or("--type is required")?;
+
+    let plaintext = Cipher::decrypt(
+        &args.text.ok_or("--text is required")?,
+        r#type,
+        args.key.as_deref(),
+        &r#type.alphabet(),
+    )?;
+
+    Ok(match args.output {
+        Output::Text => plaintext,
+        Output::Json => json!({ "type": r#type, "plaintext": plaintext }).to_string(),
+    })
+}
+
+fn random(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let args = parse(args, &["--type", "--length", "--seed", "--format"])?;
+    let quotes = read_quotes(true)?;
+
+    let cryptogram = Cryptogram::new(cryptogram::Options {
+        quotes: &quotes,
+        r#type: args.r#type,
+        length: args.length,
+        seed: args.seed,
+        rate: true,
+        ..cryptogram::Options::default()
+    })?;
+
+    Ok(write_cryptogram(&cryptogram, args.output))
+}
+
+fn test(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let args = parse(args, &["--spec", "--seed", "--format"])?;
+
+    let spec = std::fs::read_to_string(args.spec.ok_or("--spec is required")?)?;
+    let specs: Vec<QuestionSpec> = serde_json::from_str(&spec)?;
+    if specs.is_empty() {
+        return Err("A test must have at least one question".into());
+    }
+
+    let seed = args.seed.unwrap_or_else(|| thread_rng().gen());
+    let cryptograms = test::generate(&specs, seed, &read_quotes(true)?, None)?;
+
+    let questions = cryptograms
+        .iter()
+        .zip(&specs)
+        .enumerate()
+        .map(|(i, (c, spec))| (Question::new(i as i32 + 1, c, spec), c));
+
+    Ok(match args.output {
+        Output::Text => questions
+            .map(|(q, c)| {
+                let mut text = format!("Question {} ({} points)\n", q.number, q.points);
+                if let Some(hint) = &q.hint {
+                    text.push_str(&format!("Hint: {hint}\n"));
+                }
+                text.push_str(&cryptogram_text(c));
+                text
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Output::Json => {
+            let questions: Vec<_> = questions
+                .map(|(q, c)| {
+                    let mut question = cryptogram_json(c);
+                    question["number"] = json!(q.number);
+                    question["points"] = json!(q.points);
+                    question["hint"] = json!(q.hint);
+                    question
+                })
+                .collect();
+            json!({ "seed": seed, "questions": questions }).to_string()
+        }
+    })
+}
+
+/// Arguments of the `export` subcommand.
+struct ExportArgs {
+    format: ExportFormat,
+    source: ExportSource,
+    answer_key: bool,
+    output: Option<String>,
+}
+
+fn parse_export(args: &[String]) -> Result<ExportArgs, String> {
+    let mut args = args.iter();
+
+    let format = match args.next().map(String::as_str) {
+        Some("html") => ExportFormat::Html,
+        Some("latex") => ExportFormat::Latex,
+        Some(f) => return Err(format!("unknown format {f:?}")),
+        None => return Err("missing format".into()),
+    };
+
+    let mut source = None;
+    let mut answer_key = false;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--test" => {
+                let id = value()?;
+                source = Some(ExportSource::Test(
+                    id.parse().map_err(|_| format!("invalid test id {id:?}"))?,
+                ));
+            }
+            "--tokens" => {
+                let tokens = value()?
+                    .split(',')
+                    .map(|t| t.trim().parse().map_err(|_| format!("invalid token {t:?}")))
+                    .collect::<Result<_, _>>()?;
+                source = Some(ExportSource::Tokens(tokens));
+            }
+            "--answer-key" => answer_key = true,
+            "--output" => output = Some(value()?.clone()),
+            _ => return Err(format!("unknown argument {arg:?}")),
+        }
+    }
+
+    Ok(ExportArgs {
+        format,
+        source: source.ok_or("one of --test or --tokens is required")?,
+        answer_key,
+        output,
+    })
+}
+
+fn export(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let args = parse_export(args)?;
+
+    let doc = super::export(&args.source, args.format, args.answer_key)?;
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, doc)?;
+            Ok(String::new())
+        }
+        None => Ok(doc),
+    }
+}
+
+fn migrate(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let (action, args) = args.split_first().ok_or("missing migrate action")?;
+    let args = parse(args, &["--format"])?;
+
+    let store = store::open(config::get())?;
+    let migrations: Vec<_> = match action.as_str() {
+        "up" => store
+            .migrate()?
+            .into_iter()
+            .map(|m| (m, "applied"))
+            .collect(),
+        "status" => store
+            .migrations()?
+            .into_iter()
+            .map(|s| (s.migration, if s.applied { "applied" } else { "pending" }))
+            .collect(),
+        _ => return Err(format!("unknown migrate action {action:?}").into()),
+    };
+
+    Ok(match args.output {
+        Output::Text if migrations.is_empty() && action == "up" => {
+            "No pending migrations".to_string()
+        }
+        Output::Text => migrations
+            .iter()
+            .map(|(m, status)| format!("{:04} {} {status}", m.version, m.name))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => json!(migrations
+            .iter()
+            .map(|(m, status)| json!({ "version": m.version, "name": m.name, "status": status }))
+            .collect::<Vec<_>>())
+        .to_string(),
+    })
+}
+
+/// Run the subcommand named by the first of `args`.
+///
+/// Returns `None` if `args` do not start with a subcommand.
+pub fn run(args: &[String]) -> Option<ExitCode> {
+    let (command, args) = args.split_first()?;
+    let result = match command.as_str() {
+        "encrypt" => encrypt(args),
+        "decrypt" => decrypt(args),
+        "random" => random(args),
+        "test" => test(args),
+        "export" => export(args),
+        "migrate" => migrate(args),
+        _ => return None,
+    };
+
+    Some(match result {
+        Ok(out) => {
+            if !out.is_empty() {
+                println!("{}", out.trim_end());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_variant() {
+        assert!(matches!(
+            parse_variant("patristocrat-k2"),
+            Ok(Type::PatristocratK2)
+        ));
+        assert!(matches!(parse_variant("rot13"), Ok(Type::Rot13)));
+        assert!(matches!(parse_variant("short"), Ok(Length::Short)));
+        assert!(parse_variant::<Type>("enigma").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(
+            &strings(&["--type", "running-key", "--seed", "7", "--format", "json"]),
+            &["--type", "--seed", "--format"],
+        )
+        .unwrap();
+
+        assert!(matches!(args.r#type, Some(Type::RunningKey)));
+        assert_eq!(args.seed, Some(7));
+        assert_eq!(args.output, Output::Json);
+
+        assert!(parse(&strings(&["--spec", "file"]), &["--type"]).is_err());
+        assert!(parse(&strings(&["--type"]), &["--type"]).is_err());
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let out = decrypt(&strings(&[
+            "--type",
+            "caesar",
+            "--key",
+            "3",
+            "--text",
+            "Dwwdfn dw gdzq!",
+        ]))
+        .unwrap();
+
+        assert_eq!(out, "Attack at dawn!");
+    }
+}
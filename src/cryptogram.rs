@@ -1,15 +1,26 @@
 //! This module defines the Cryptogram object for the public interface.
 
-use super::ciphers::{Cipher, CipherResult};
+use super::auth::Role;
+use super::ciphers::{Alphabet, Cipher, CipherError, CipherResult, ErrorKind, Key};
+use super::difficulty::{self, DifficultyRange};
+use super::format::Format;
+use super::normalize::Normalization;
 use super::quotes;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use super::solver;
+use super::token::Token;
+use super::Context;
+use juniper::serde::{Deserialize, Serialize};
+use rand::prelude::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
 
 /// Describe the type of cipher used to encrypt a [`Cryptogram`]
 ///
 /// Each of the variants has an accompanying function with a lowercased name.
 /// For example, [`Type::Identity`] has the function [`crate::ciphers::identity`].
-#[derive(GraphQLEnum, Debug, Copy, Clone)]
+#[derive(GraphQLEnum, Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(crate = "juniper::serde")]
 pub enum Type {
     /// Returns the plaintext unchanged. See [`crate::ciphers::identity`] for more details.
     Identity,
@@ -32,19 +43,81 @@ pub enum Type {
     //    Cryptarithm,
     /// Polyalphabetic substitution, spaces ignored. See ['crate::ciphers::hill`] for more details.
     Hill,
+    /// Letters replaced by their row and column in a 5x5 square. See
+    /// [`crate::ciphers::polybius::polybius`] for more details.
+    Polybius,
+    /// Letters replaced by groups of dots for their row and column. See
+    /// [`crate::ciphers::polybius::tap_code`] for more details.
+    TapCode,
+    /// Letters replaced by pigpen symbol identifiers. See [`crate::ciphers::polybius::pigpen`]
+    /// for more details.
+    Pigpen,
+    /// Vigenère cipher keyed by another quote. See [`crate::ciphers::running_key::running_key`]
+    /// for more details.
+    RunningKey,
+    /// Words replaced by their position in the quote corpus. See [`crate::ciphers::book::book`]
+    /// for more details.
+    Book,
     // TODO: Add xenocrypt
 }
 
+impl Type {
+    /// Whether the ciphertext is made of letters, with the plaintext's digits and punctuation
+    /// left in place.
+    ///
+    /// The other types encode letters as digits or symbols and drop everything else.
+    pub(crate) fn ciphertext_is_letters(self) -> bool {
+        use Type::{Book, Morbit, Pigpen, Polybius, TapCode};
+        !matches!(self, Morbit | Polybius | TapCode | Pigpen | Book)
+    }
+
+    /// Whether each letter is always encrypted to the same letter.
+    pub(crate) fn is_monoalphabetic(self) -> bool {
+        use Type::{
+            Aristocrat, Caesar, Identity, Patristocrat, PatristocratK1, PatristocratK2, Rot13,
+        };
+        matches!(
+            self,
+            Identity | Rot13 | Caesar | Aristocrat | Patristocrat | PatristocratK1 | PatristocratK2
+        )
+    }
+
+    /// The alphabet the cipher operates on.
+    pub(crate) fn alphabet(self) -> Alphabet {
+        match self {
+            Type::Polybius => Alphabet::latin25(),
+            Type::TapCode => Alphabet::english().with_merge('k', 'c'),
+            _ => Alphabet::english(),
+        }
+    }
+}
+
+/// Types are stored in the database by variant name.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl FromStr for Type {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.into()))
+    }
+}
+
 /// The length of a cipher.
 ///
 /// The ranges for each variant are start inclusive and end exclusive.
-#[derive(GraphQLEnum, Debug, Copy, Clone)]
+#[derive(GraphQLEnum, Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(crate = "juniper::serde")]
 pub enum Length {
-    /// Quotations ranging from 60 to 90 bytes.
+    /// Quotations ranging from 60 to 90 characters.
     Short,
-    /// Quotations ranging from 90 to 120 bytes.
+    /// Quotations ranging from 90 to 120 characters.
     Medium,
-    /// Quotations ranging from 120 to 150 bytes.
+    /// Quotations ranging from 120 to 150 characters.
     Long,
 }
 
@@ -55,16 +128,25 @@ pub struct Answer {
 
     /// The key used to encrypt, if applicable.
     pub key: Option<String>,
+
+    /// The full key, such as the substitution alphabet or the Hill matrix.
+    ///
+    /// Cryptograms created before full keys were stored do not have one.
+    pub structured_key: Option<Key>,
 }
 
 impl Answer {
     #[must_use]
-    pub fn new(plaintext: String, key: Option<String>) -> Self {
-        Self { plaintext, key }
+    pub fn new(plaintext: String, key: Option<String>, structured_key: Option<Key>) -> Self {
+        Self {
+            plaintext,
+            key,
+            structured_key,
+        }
     }
 }
 
-#[derive(GraphQLObject)]
+/// A cryptogram, as made by [`Cryptogram::new`].
 pub struct Cryptogram {
     /// The encrypted text.
     pub ciphertext: String,
@@ -74,91 +156,254 @@ pub struct Cryptogram {
     pub length: Length,
     /// The author of the quote.
     pub author: Option<String>,
-
+    /// The seed used to generate the cryptogram.
+    pub seed: i32,
     /// Token to request the plaintext.
-    pub token: i32,
-
-    /// The key used to encrypt, if applicable.
-    #[graphql(skip)]
-    pub key: Option<String>,
-
+    pub token: Token,
+    /// The key used to encrypt.
+    pub key: Key,
     /// The plaintext
-    #[graphql(skip)]
     pub plaintext: String,
-
+    /// The ID of the quote, unless the plaintext was given.
+    pub quote_id: Option<i32>,
     /// Character frequencies, if applicable
     pub frequencies: Option<Vec<i32>>,
+    /// How hard the cryptogram is to solve, if it was rated.
+    pub difficulty: Option<f64>,
 }
 
+#[graphql_object(context = Context)]
 impl Cryptogram {
-    /// Create a Cryptogram from plaintext, length, and type
+    /// The encrypted text.
+    fn ciphertext(&self) -> &str {
+        &self.ciphertext
+    }
+
+    /// The type of cipher used.
+    fn r#type(&self) -> Type {
+        self.r#type
+    }
+
+    /// The length of the plaintext.
+    fn length(&self) -> Length {
+        self.length
+    }
+
+    /// The author of the quote.
+    fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// The seed used to generate the cryptogram. Requesting a cryptogram with the same seed, type
+    /// and length gives the same cryptogram.
+    ///
+    /// Only proctors can see seeds, since a seed gives away the quote. Other roles get null.
+    fn seed(&self, context: &Context) -> Option<i32> {
+        (context.role >= Role::Proctor).then_some(self.seed)
+    }
+
+    /// Token to request the plaintext. Every cryptogram has a new random token, even one
+    /// regenerated from the same seed.
+    fn token(&self) -> &Token {
+        &self.token
+    }
+
+    /// Character frequencies, if applicable
+    fn frequencies(&self) -> Option<&[i32]> {
+        self.frequencies.as_deref()
+    }
+
+    /// How hard the cryptogram is to solve, from 0 (easiest) to 1 (hardest).
+    ///
+    /// Only monoalphabetic substitution ciphers are rated.
+    fn difficulty(&self) -> Option<f64> {
+        self.difficulty
+    }
+}
+
+/// The most quotes tried when looking for a cryptogram in a difficulty range.
+const MAX_DIFFICULTY_ATTEMPTS: usize = 20;
+
+/// Options for creating a [`Cryptogram`]. See [`Cryptogram::new`] for the defaults.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Options<'a> {
+    /// The enabled quotes of the corpus, which random plaintexts and quote keys are picked from.
+    pub quotes: &'a [quotes::Quote],
+    pub plaintext: Option<String>,
+    pub length: Option<Length>,
+    pub r#type: Option<Type>,
+    pub key: Option<String>,
+    pub format: Option<Format>,
+    pub normalization: Option<Normalization>,
+    pub seed: Option<i32>,
+    pub difficulty: Option<DifficultyRange>,
+    /// Rate the difficulty even if no `difficulty` range is given. Rating runs the solver, so it
+    /// is skipped unless the difficulty is needed.
+    pub rate: bool,
+    /// Constraints on the random quote. Ignored if `plaintext` is given.
+    pub quote_filter: Option<quotes::QuoteFilter>,
+    /// The IDs of quotes already served, which are only picked once every other quote has been.
+    pub served: Option<&'a HashSet<i32>>,
+    /// The IDs of quotes that must not be picked, such as those of the other questions of a test.
+    pub used: Option<&'a HashSet<i32>>,
+}
+
+impl Cryptogram {
+    /// Create a Cryptogram from `options`
     ///
     /// If plaintext is not given, then a random quotation is selected.
     /// The default `length` is [`Length::Medium`] and the default `r#type`
     /// is [`Type::Identity`], though this may change in the future.
-    pub(crate) fn new(
-        plaintext: Option<String>,
-        length: Option<Length>,
-        r#type: Option<Type>,
-        key: Option<String>,
-    ) -> CipherResult<Self> {
+    /// The plaintext is normalized with `normalization` before encryption, and the ciphertext is
+    /// formatted with `format`. See [`Normalization`] and [`Format`] for the defaults.
+    ///
+    /// Quote selection and key generation are driven by an RNG seeded with `seed`. If `seed` is
+    /// not given, a random seed is chosen.
+    ///
+    /// If `difficulty` is given, new seeds are drawn from the first one until the cryptogram falls
+    /// in the range, giving up after [`MAX_DIFFICULTY_ATTEMPTS`] tries. The returned cryptogram has
+    /// the seed that produced it.
+    ///
+    /// Rating runs the solver, so a `plaintext` longer than [`solver::MAX_CIPHERTEXT_LENGTH`] is
+    /// an `InvalidArgument` error if the cryptogram is rated.
+    pub(crate) fn new(options: Options<'_>) -> CipherResult<Self> {
+        if let Some(plaintext) = &options.plaintext {
+            if (options.rate || options.difficulty.is_some())
+                && plaintext.chars().count() > solver::MAX_CIPHERTEXT_LENGTH
+            {
+                return Err(CipherError::new(
+                    ErrorKind::InvalidArgument,
+                    format!(
+                        "The plaintext of a rated cryptogram can be at most {} characters long",
+                        solver::MAX_CIPHERTEXT_LENGTH
+                    ),
+                )
+                .with_field("plaintext"));
+            }
+        }
+
+        let seed = options.seed.unwrap_or_else(|| thread_rng().gen());
+
+        let range = match &options.difficulty {
+            Some(range) => range,
+            None => return Self::from_seed(&options, seed),
+        };
+
+        let mut seeds = StdRng::seed_from_u64(seed as u64);
+        let mut seed = seed;
+        for _ in 0..MAX_DIFFICULTY_ATTEMPTS {
+            let cryptogram = Self::from_seed(&options, seed)?;
+            match cryptogram.difficulty {
+                Some(d) if range.contains(d) => return Ok(cryptogram),
+                Some(d) => log::debug!("Difficulty {d} of seed {seed} is out of range"),
+                None => {
+                    return Err(CipherError::new(
+                        ErrorKind::InvalidDifficulty,
+                        format!("{:?} cryptograms cannot be rated", cryptogram.r#type),
+                    ))
+                }
+            }
+            seed = seeds.gen();
+        }
+
+        Err(CipherError::new(
+            ErrorKind::InvalidDifficulty,
+            format!("No cryptogram found in the difficulty range after {MAX_DIFFICULTY_ATTEMPTS} attempts"),
+        ))
+    }
+
+    /// Create a Cryptogram from `options`, using `seed` instead of `options.seed`.
+    fn from_seed(options: &Options<'_>, seed: i32) -> CipherResult<Self> {
         use Type::{Aristocrat, Caesar, Identity, Patristocrat, PatristocratK1, PatristocratK2};
-        let r#type = r#type.unwrap_or(Identity);
+        let r#type = options.r#type.unwrap_or(Identity);
+
+        let length = options.length.unwrap_or(Length::Medium);
 
-        let length = length.unwrap_or(Length::Medium);
+        let rng = &mut StdRng::seed_from_u64(seed as u64);
 
-        let quote = match plaintext {
-            Some(t) => quotes::Quote::new(t, None),
-            None => quotes::fetch_quote(length),
+        let (text, author, quote_id) = match &options.plaintext {
+            Some(t) => (t.clone(), None, None),
+            None => {
+                let quote = quotes::fetch_quote(
+                    options.quotes,
+                    length,
+                    options.quote_filter.as_ref(),
+                    options.served,
+                    options.used,
+                    rng,
+                )?;
+                (quote.text.clone(), quote.author.clone(), Some(quote.id))
+            }
         };
+        let plaintext = options
+            .normalization
+            .clone()
+            .unwrap_or_default()
+            .apply(&text);
 
-        let cipher = Cipher::encrypt(&quote.text, r#type, key)?;
+        let alphabet = r#type.alphabet();
+        let cipher = Cipher::encrypt(
+            &plaintext,
+            r#type,
+            options.key.clone(),
+            &alphabet,
+            options.quotes,
+            rng,
+        )?;
 
         let frequencies = match r#type {
             Identity | Caesar | Aristocrat | Patristocrat | PatristocratK1 | PatristocratK2 => {
-                Some(frequencies(&cipher.ciphertext))
+                Some(frequencies(&cipher.ciphertext, &alphabet))
             }
             _ => None,
         };
 
+        let difficulty = if options.rate || options.difficulty.is_some() {
+            difficulty::rate(&plaintext, &cipher.ciphertext, r#type, rng)
+        } else {
+            None
+        };
+
         Ok(Self {
-            ciphertext: cipher.ciphertext.to_uppercase(),
+            ciphertext: options
+                .format
+                .clone()
+                .unwrap_or_default()
+                .apply(&cipher.ciphertext, r#type),
             r#type,
             length,
-            author: quote.author,
-            token: compute_hash(&cipher.ciphertext),
+            author,
+            seed,
+            token: Token::generate(),
             key: cipher.key,
-            plaintext: quote.text,
+            plaintext,
+            quote_id,
             frequencies,
+            difficulty,
         })
     }
 }
 
-fn frequencies(s: &str) -> Vec<i32> {
-    let mut freqs = vec![0; 26];
-    for b in s.to_uppercase().bytes() {
-        if b.is_ascii_alphabetic() {
-            freqs[(b - b'A') as usize] += 1;
+/// Count the occurrences of each letter of `alphabet` in `s`.
+fn frequencies(s: &str, alphabet: &Alphabet) -> Vec<i32> {
+    let mut freqs = vec![0; alphabet.len()];
+    for c in s.chars() {
+        if let Some(i) = alphabet.index_of(c) {
+            freqs[i] += 1;
         }
     }
 
     freqs
 }
 
-fn compute_hash(s: &str) -> i32 {
-    let mut hasher = DefaultHasher::new();
-    s.hash(&mut hasher);
-    hasher.finish() as i32
-}
-
 #[cfg(test)]
 mod tests {
-    use super::frequencies;
+    use super::{frequencies, quotes, Alphabet, Cryptogram, DifficultyRange, Options, Type};
 
     #[test]
     fn test_frequencies() {
         let input = "aaaaabbccac";
-        let output = frequencies(input);
+        let output = frequencies(input, &Alphabet::english());
         let mut ans = vec![0; 26];
         ans[0] = input.chars().filter(|c| *c == 'a').count() as i32;
         ans[1] = input.chars().filter(|c| *c == 'b').count() as i32;
@@ -166,4 +411,108 @@ mod tests {
 
         assert_eq!(output, ans);
     }
+
+    #[test]
+    fn test_type_round_trip() {
+        let r#type: Type = Type::PatristocratK1.to_string().parse().unwrap();
+
+        assert!(matches!(r#type, Type::PatristocratK1));
+        assert!("Enigma".parse::<Type>().is_err());
+    }
+
+    #[test]
+    fn test_seed_reproducible() {
+        let new = |seed| {
+            Cryptogram::new(Options {
+                plaintext: Some("The quick brown fox jumps over the lazy dog".into()),
+                r#type: Some(Type::Aristocrat),
+                seed,
+                ..Options::default()
+            })
+            .unwrap()
+        };
+
+        assert_eq!(new(Some(42)).ciphertext, new(Some(42)).ciphertext);
+        assert_eq!(new(Some(42)).seed, 42);
+    }
+
+    #[test]
+    fn test_random_quote() {
+        let text = "Two things are infinite: the universe and human stupidity; and I'm not sure about the universe.";
+        let quotes = [quotes::Quote::new(
+            1,
+            quotes::QuoteInput {
+                text: text.into(),
+                author: Some("Albert Einstein".into()),
+                ..quotes::QuoteInput::default()
+            },
+        )];
+
+        let cryptogram = Cryptogram::new(Options {
+            quotes: &quotes,
+            ..Options::default()
+        })
+        .unwrap();
+        assert_eq!(cryptogram.plaintext, text);
+        assert_eq!(cryptogram.author.as_deref(), Some("Albert Einstein"));
+
+        assert!(Cryptogram::new(Options::default()).is_err());
+    }
+
+    #[test]
+    fn test_difficulty_unrated() {
+        let res = Cryptogram::new(Options {
+            plaintext: Some("The quick brown fox jumps over the lazy dog".into()),
+            r#type: Some(Type::Polybius),
+            difficulty: Some(DifficultyRange::default()),
+            ..Options::default()
+        });
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_rate_only_when_asked() {
+        let options = Options {
+            plaintext: Some("The quick brown fox jumps over the lazy dog".into()),
+            r#type: Some(Type::Aristocrat),
+            seed: Some(7),
+            ..Options::default()
+        };
+
+        let unrated = Cryptogram::new(options.clone()).unwrap();
+        let rated = Cryptogram::new(Options {
+            rate: true,
+            ..options
+        })
+        .unwrap();
+
+        assert_eq!(unrated.difficulty, None);
+        assert!(rated.difficulty.is_some());
+        assert_eq!(unrated.ciphertext, rated.ciphertext);
+    }
+
+    #[test]
+    fn test_rate_length_limit() {
+        let options = Options {
+            plaintext: Some("a".repeat(crate::solver::MAX_CIPHERTEXT_LENGTH + 1)),
+            r#type: Some(Type::Aristocrat),
+            ..Options::default()
+        };
+
+        assert!(Cryptogram::new(options.clone()).is_ok());
+        for options in [
+            Options {
+                rate: true,
+                ..options.clone()
+            },
+            Options {
+                difficulty: Some(DifficultyRange::default()),
+                ..options
+            },
+        ] {
+            let err = Cryptogram::new(options).map(|_| ()).unwrap_err();
+            assert!(err.to_string().starts_with("InvalidArgument"));
+        }
+    }
 }
@@ -0,0 +1,253 @@
+//! This module rates how hard a cryptogram is to solve.
+//!
+//! Only monoalphabetic substitution ciphers are rated, since the rating measures how much help
+//! frequency analysis and word patterns give a solver. Each feature is scaled from 0 (easy) to
+//! 1 (hard), and the difficulty is their weighted average:
+//!
+//! - how far the letter distribution of the plaintext is from English,
+//! - how many different letters the plaintext uses,
+//! - how few short words give away "a", "I", "the" and so on,
+//! - how few words are repeated,
+//! - how badly [`solver::solve`] does, and how many keys it tries before its best guess.
+//!
+//! Caesar ciphers and ROT13 only have 26 keys, so their rating is scaled down.
+
+use super::ciphers::Alphabet;
+use super::cryptogram::Type;
+use super::solver;
+use juniper::serde::Deserialize;
+use rand::prelude::*;
+use std::collections::HashMap;
+
+/// Frequencies of the letters in English text, in percent, from A to Z.
+const ENGLISH_FREQUENCIES: [f64; 26] = [
+    8.2, 1.5, 2.8, 4.3, 12.7, 2.2, 2.0, 6.1, 7.0, 0.15, 0.77, 4.0, 2.4, 6.7, 7.5, 1.9, 0.095, 6.0,
+    6.3, 9.1, 2.8, 0.98, 2.4, 0.15, 2.0, 0.074,
+];
+
+/// The fraction of words in English text that have at most 3 letters, roughly.
+const ENGLISH_SHORT_WORDS: f64 = 0.4;
+
+/// An inclusive range of difficulties.
+#[derive(GraphQLInputObject, Deserialize, Debug, Default, Clone)]
+#[serde(crate = "juniper::serde")]
+pub struct DifficultyRange {
+    /// The lowest difficulty, from 0 to 1. Defaults to 0.
+    pub min: Option<f64>,
+    /// The highest difficulty, from 0 to 1. Defaults to 1.
+    pub max: Option<f64>,
+}
+
+impl DifficultyRange {
+    /// Whether `difficulty` is in the range.
+    pub(crate) fn contains(&self, difficulty: f64) -> bool {
+        self.min.unwrap_or(0.0) <= difficulty && difficulty <= self.max.unwrap_or(1.0)
+    }
+}
+
+/// Rate how hard `ciphertext`, the encryption of `plaintext` with `r#type`, is to solve.
+///
+/// Returns a difficulty from 0 (easiest) to 1 (hardest), or `None` if `r#type` is not a
+/// monoalphabetic substitution cipher. `rng` drives the solver, which is the slow part, so only
+/// rate cryptograms whose difficulty is needed.
+pub(crate) fn rate<R>(plaintext: &str, ciphertext: &str, r#type: Type, rng: &mut R) -> Option<f64>
+where
+    R: Rng + ?Sized,
+{
+    use Type::{Aristocrat, Caesar, Patristocrat, PatristocratK1, PatristocratK2, Rot13};
+
+    let (scale, word_breaks) = match r#type {
+        Rot13 | Caesar => (0.25, true),
+        Aristocrat => (1.0, true),
+        Patristocrat | PatristocratK1 | PatristocratK2 => (1.0, false),
+        _ => return None,
+    };
+
+    let alphabet = r#type.alphabet();
+    let letters: Vec<_> = plaintext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+    if letters.is_empty() {
+        return Some(0.0);
+    }
+
+    // the ciphertext of a patristocrat hides where the words are
+    let short = if word_breaks {
+        short_words(plaintext, &alphabet)
+    } else {
+        1.0
+    };
+
+    let difficulty = 0.15 * distribution(&letters, &alphabet)
+        + 0.15 * unique_letters(&letters, &alphabet)
+        + 0.2 * short
+        + 0.1 * repeated_words(plaintext, &alphabet)
+        + 0.4 * solver_effort(&letters, ciphertext, &alphabet, rng);
+
+    Some((scale * difficulty).clamp(0.0, 1.0))
+}
+
+/// The frequency of `symbol` in English text, in percent, if it is an English letter.
+fn english_frequency(symbol: char) -> Option<f64> {
+    symbol
+        .is_ascii_lowercase()
+        .then(|| ENGLISH_FREQUENCIES[(symbol as u8 - b'a') as usize])
+}
+
+/// How far the letter distribution of `letters`, which are indexes into `alphabet`, is from
+/// English.
+///
+/// This is the chi-squared statistic per letter, mapped from 0..infinity to 0..1. Symbols that are
+/// not English letters are left out.
+fn distribution(letters: &[usize], alphabet: &Alphabet) -> f64 {
+    let mut counts = vec![0.0; alphabet.len()];
+    for &l in letters {
+        counts[l] += 1.0;
+    }
+
+    let n = letters.len() as f64;
+    let chi_squared: f64 = counts
+        .iter()
+        .zip(alphabet.symbols())
+        .filter_map(|(&observed, &symbol)| {
+            let expected = n * english_frequency(symbol)? / 100.0;
+            Some((observed - expected).powi(2) / expected)
+        })
+        .sum();
+
+    let per_letter = chi_squared / n;
+    per_letter / (per_letter + 1.0)
+}
+
+/// The fraction of `alphabet` used by `letters`.
+fn unique_letters(letters: &[usize], alphabet: &Alphabet) -> f64 {
+    let mut present = vec![false; alphabet.len()];
+    for &l in letters {
+        present[l] = true;
+    }
+
+    present.iter().filter(|&&p| p).count() as f64 / alphabet.len() as f64
+}
+
+/// The letters of each word of `s`, lowercased.
+fn words(s: &str, alphabet: &Alphabet) -> Vec<String> {
+    s.split_whitespace()
+        .map(|w| {
+            w.chars()
+                .filter(|&c| alphabet.index_of(c).is_some())
+                .map(|c| alphabet.fold(c))
+                .collect::<String>()
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// How few words of `s` have at most 3 letters, compared to English.
+fn short_words(s: &str, alphabet: &Alphabet) -> f64 {
+    let words = words(s, alphabet);
+    if words.is_empty() {
+        return 1.0;
+    }
+
+    let short = words.iter().filter(|w| w.chars().count() <= 3).count();
+    let fraction = short as f64 / words.len() as f64;
+    (1.0 - fraction / ENGLISH_SHORT_WORDS).max(0.0)
+}
+
+/// One minus the fraction of words of `s` that appear more than once.
+fn repeated_words(s: &str, alphabet: &Alphabet) -> f64 {
+    let words = words(s, alphabet);
+    if words.is_empty() {
+        return 1.0;
+    }
+
+    let mut counts = HashMap::new();
+    for w in &words {
+        *counts.entry(w.as_str()).or_insert(0) += 1;
+    }
+
+    let repeated = words.iter().filter(|w| counts[w.as_str()] > 1).count();
+    1.0 - repeated as f64 / words.len() as f64
+}
+
+/// How badly the solver does on `ciphertext`, whose plaintext is `letters`.
+///
+/// Combines the fraction of letters the solver gets wrong with how many keys it tries before
+/// finding its best key.
+fn solver_effort<R>(letters: &[usize], ciphertext: &str, alphabet: &Alphabet, rng: &mut R) -> f64
+where
+    R: Rng + ?Sized,
+{
+    let solution = solver::solve(ciphertext, rng);
+
+    let solved: Vec<_> = solution
+        .plaintext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+    let correct = solved.iter().zip(letters).filter(|(a, b)| a == b).count();
+    let accuracy = correct as f64 / letters.len() as f64;
+
+    let effort = solution.iterations as f64 / solver::MAX_ITERATIONS as f64;
+
+    0.7 * (1.0 - accuracy) + 0.3 * effort.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PLAINTEXT: &str = "Ask not what your country can do for you, ask what you can do for \
+        your country.";
+
+    #[test]
+    fn test_range() {
+        let range = DifficultyRange {
+            min: Some(0.2),
+            max: None,
+        };
+
+        assert!(!range.contains(0.1));
+        assert!(range.contains(0.2));
+        assert!(range.contains(1.0));
+    }
+
+    #[test]
+    fn test_distribution() {
+        let alphabet = Alphabet::english();
+        let english: Vec<_> = "thequickbrownfoxjumpsoverthelazydog"
+            .chars()
+            .filter_map(|c| alphabet.index_of(c))
+            .collect();
+        let skewed = vec![25; 35];
+
+        assert!(distribution(&english, &alphabet) < distribution(&skewed, &alphabet));
+        assert_eq!(unique_letters(&english, &alphabet), 1.0);
+    }
+
+    #[test]
+    fn test_words() {
+        let alphabet = Alphabet::english();
+
+        assert_eq!(short_words("a an the", &alphabet), 0.0);
+        assert_eq!(short_words("quick brown", &alphabet), 1.0);
+        assert_eq!(repeated_words("the cat, the dog", &alphabet), 0.5);
+    }
+
+    #[test]
+    fn test_rate() {
+        let rng = &mut StdRng::seed_from_u64(0);
+        let ciphertext: String = PLAINTEXT
+            .chars()
+            .map(|c| Alphabet::english().shift(c, 3).unwrap_or(c))
+            .collect();
+
+        let caesar = rate(PLAINTEXT, &ciphertext, Type::Caesar, rng).unwrap();
+        let aristocrat = rate(PLAINTEXT, &ciphertext, Type::Aristocrat, rng).unwrap();
+
+        assert!((0.0..=1.0).contains(&aristocrat));
+        assert!(caesar < aristocrat);
+        assert_eq!(rate(PLAINTEXT, &ciphertext, Type::Hill, rng), None);
+    }
+}
@@ -0,0 +1,510 @@
+//! This module renders tests as printable documents, in HTML or LaTeX.
+//!
+//! Both formats are self-contained. Each question has its ciphertext in a grid with blank answer
+//! boxes underneath, a frequency table for substitution ciphers, and a key table for Morbit. The
+//! answer key is rendered as a separate document with the plaintexts and keys filled in.
+
+use super::ciphers::{Alphabet, Key};
+use super::cryptogram::Type;
+use super::test::Question;
+use super::token::Token;
+use std::fmt::Write;
+
+/// The most cells in a row of a ciphertext grid.
+const GRID_WIDTH: usize = 25;
+
+/// The format of an exported document.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Latex,
+}
+
+/// Where to find the questions to export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportSource {
+    /// Every question of a test, by ID.
+    Test(i32),
+    /// Cryptograms by token, numbered in order.
+    Tokens(Vec<Token>),
+}
+
+/// A question with everything needed to print it and its answer.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub question: Question,
+    pub plaintext: String,
+    pub key: Key,
+}
+
+/// Render `entries` in `format`. If `answer_key` is true, the answer key is rendered instead of
+/// the test.
+pub(crate) fn render(entries: &[Entry], format: ExportFormat, answer_key: bool) -> String {
+    match format {
+        ExportFormat::Html => html(entries, answer_key),
+        ExportFormat::Latex => latex(entries, answer_key),
+    }
+}
+
+/// A cell of a ciphertext grid.
+#[derive(Debug, PartialEq, Eq)]
+enum Cell {
+    /// A ciphertext symbol, with an answer box underneath if `answer` is true.
+    Symbol { text: String, answer: bool },
+    /// The space between two words.
+    Gap,
+}
+
+/// Split `ciphertext` into the cells of its grid.
+///
+/// Ciphers whose ciphertext is made of letters have a cell per character. Morbit has a cell per
+/// digit, and the other ciphers have a cell per symbol, with "/" between words.
+fn cells(ciphertext: &str, r#type: Type) -> Vec<Cell> {
+    fn push_gap(cells: &mut Vec<Cell>) {
+        if !matches!(cells.last(), None | Some(Cell::Gap)) {
+            cells.push(Cell::Gap);
+        }
+    }
+
+    let mut cells = Vec::new();
+    let morbit = matches!(r#type, Type::Morbit);
+
+    if r#type.ciphertext_is_letters() || morbit {
+        for c in ciphertext.chars() {
+            if c.is_whitespace() {
+                push_gap(&mut cells);
+            } else {
+                // digits kept from the plaintext are not encrypted, except in Morbit
+                cells.push(Cell::Symbol {
+                    text: c.to_string(),
+                    answer: c.is_alphabetic() || morbit,
+                });
+            }
+        }
+    } else {
+        // symbols are separated by whitespace, which the grid already shows
+        for symbol in ciphertext.split_whitespace() {
+            if symbol == "/" {
+                push_gap(&mut cells);
+            } else {
+                cells.push(Cell::Symbol {
+                    text: symbol.to_string(),
+                    answer: true,
+                });
+            }
+        }
+    }
+
+    cells
+}
+
+/// Split `cells` into rows of at most [`GRID_WIDTH`] cells, without starting a row with a gap.
+fn rows(cells: &[Cell]) -> Vec<&[Cell]> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < cells.len() {
+        if cells[start] == Cell::Gap {
+            start += 1;
+            continue;
+        }
+
+        let end = (start + GRID_WIDTH).min(cells.len());
+        rows.push(&cells[start..end]);
+        start = end;
+    }
+
+    rows
+}
+
+/// Count the letters of `ciphertext`, if `r#type` is a substitution cipher.
+fn frequencies(ciphertext: &str, r#type: Type) -> Option<Vec<(char, usize)>> {
+    if !r#type.is_monoalphabetic() {
+        return None;
+    }
+
+    let alphabet = Alphabet::english();
+    let mut counts = vec![0; alphabet.len()];
+    for c in ciphertext.chars() {
+        if let Some(i) = alphabet.index_of(c) {
+            counts[i] += 1;
+        }
+    }
+
+    Some(
+        alphabet
+            .symbols()
+            .iter()
+            .map(char::to_ascii_uppercase)
+            .zip(counts)
+            .collect(),
+    )
+}
+
+/// The Morbit key table, with the Morse pairs filled in if `answer_key` is true.
+fn morbit_table(entry: &Entry, answer_key: bool) -> Option<Vec<(usize, String)>> {
+    if !matches!(entry.question.r#type, Type::Morbit) {
+        return None;
+    }
+
+    let pairs = entry.key.morbit.clone().unwrap_or_default();
+    Some(
+        (1..=9)
+            .map(|digit| {
+                let pair = match pairs.get(digit - 1) {
+                    Some(p) if answer_key => p.clone(),
+                    _ => String::new(),
+                };
+                (digit, pair)
+            })
+            .collect(),
+    )
+}
+
+/// The lines of the answer to `entry`, as (label, value) pairs.
+fn answer_lines(entry: &Entry) -> Vec<(&'static str, String)> {
+    let key = &entry.key;
+    let mut lines = vec![("Plaintext", entry.plaintext.clone())];
+
+    if let Some(keyword) = &key.keyword {
+        lines.push(("Key", keyword.clone()));
+    }
+    if let Some(shift) = key.shift {
+        lines.push(("Shift", shift.to_string()));
+    }
+    if let Some(mapping) = &key.mapping {
+        let alphabet: String = Alphabet::english()
+            .symbols()
+            .iter()
+            .take(mapping.chars().count())
+            .collect();
+        lines.push(("Plain", alphabet.to_uppercase()));
+        lines.push(("Cipher", mapping.to_uppercase()));
+    }
+    if let Some(matrix) = &key.matrix {
+        let rows: Vec<_> = matrix
+            .iter()
+            .map(|row| row.iter().map(i32::to_string).collect::<Vec<_>>().join(" "))
+            .collect();
+        lines.push(("Matrix", rows.join(" / ")));
+    }
+    if let Some(square) = &key.square {
+        lines.push(("Square", square.join(" / ").to_uppercase()));
+    }
+
+    lines
+}
+
+/// The title of a question, such as "Question 1 (250 points): Aristocrat".
+fn title(question: &Question) -> String {
+    format!(
+        "Question {} ({} points): {}",
+        question.number, question.points, question.r#type
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; }
+section { break-inside: avoid; margin-bottom: 2em; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+.grid td { font-family: monospace; text-align: center; min-width: 1.5em; padding: 0 0.1em; }
+.grid td.box { border: 1px solid black; height: 1.8em; }
+.grid tr.answers td { padding-bottom: 0.6em; }
+.table td, .table th { border: 1px solid black; min-width: 1.5em; text-align: center; }
+.hint { font-style: italic; }";
+
+fn html(entries: &[Entry], answer_key: bool) -> String {
+    let heading = if answer_key { "Answer Key" } else { "Test" };
+
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">").unwrap();
+    writeln!(
+        out,
+        "<title>{heading}</title>\n<style>\n{HTML_STYLE}\n</style>"
+    )
+    .unwrap();
+    writeln!(out, "</head>\n<body>\n<h1>{heading}</h1>").unwrap();
+
+    for entry in entries {
+        let question = &entry.question;
+        writeln!(out, "<section>").unwrap();
+        writeln!(out, "<h2>{}</h2>", escape_html(&title(question))).unwrap();
+        if let Some(hint) = &question.hint {
+            writeln!(out, "<p class=\"hint\">{}</p>", escape_html(hint)).unwrap();
+        }
+
+        if answer_key {
+            writeln!(out, "<table class=\"table\">").unwrap();
+            for (label, value) in answer_lines(entry) {
+                writeln!(
+                    out,
+                    "<tr><th>{label}</th><td>{}</td></tr>",
+                    escape_html(&value)
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        } else {
+            writeln!(out, "<table class=\"grid\">").unwrap();
+            for row in rows(&cells(&question.ciphertext, question.r#type)) {
+                let mut symbols = String::new();
+                let mut boxes = String::new();
+                for cell in row {
+                    match cell {
+                        Cell::Symbol { text, answer } => {
+                            write!(symbols, "<td>{}</td>", escape_html(text)).unwrap();
+                            boxes.push_str(if *answer {
+                                "<td class=\"box\"></td>"
+                            } else {
+                                "<td></td>"
+                            });
+                        }
+                        Cell::Gap => {
+                            symbols.push_str("<td></td>");
+                            boxes.push_str("<td></td>");
+                        }
+                    }
+                }
+                writeln!(
+                    out,
+                    "<tr>{symbols}</tr>\n<tr class=\"answers\">{boxes}</tr>"
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+
+            if let Some(freqs) = frequencies(&question.ciphertext, question.r#type) {
+                let letters: String = freqs.iter().map(|(c, _)| format!("<th>{c}</th>")).collect();
+                let counts: String = freqs.iter().map(|(_, n)| format!("<td>{n}</td>")).collect();
+                let blanks = "<td></td>".repeat(freqs.len());
+                writeln!(out, "<table class=\"table\">").unwrap();
+                writeln!(out, "<tr><th></th>{letters}</tr>").unwrap();
+                writeln!(out, "<tr><th>Frequency</th>{counts}</tr>").unwrap();
+                writeln!(out, "<tr><th>Replacement</th>{blanks}</tr>").unwrap();
+                writeln!(out, "</table>").unwrap();
+            }
+        }
+
+        if let Some(table) = morbit_table(entry, answer_key) {
+            let digits: String = table.iter().map(|(d, _)| format!("<th>{d}</th>")).collect();
+            let pairs: String = table
+                .iter()
+                .map(|(_, p)| format!("<td>{}</td>", escape_html(p)))
+                .collect();
+            writeln!(out, "<table class=\"table\">").unwrap();
+            writeln!(out, "<tr>{digits}</tr>\n<tr>{pairs}</tr>").unwrap();
+            writeln!(out, "</table>").unwrap();
+        }
+
+        writeln!(out, "</section>").unwrap();
+    }
+
+    writeln!(out, "</body>\n</html>").unwrap();
+    out
+}
+
+fn escape_latex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '^' => out.push_str("\\textasciicircum{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            // keep "--" from becoming a dash
+            '-' => out.push_str("-{}"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+const LATEX_PREAMBLE: &str = "\\documentclass{article}
+\\usepackage[margin=0.75in]{geometry}
+\\usepackage[T1]{fontenc}
+\\setlength{\\parindent}{0pt}
+\\newcommand{\\answerbox}{\\framebox[1.4em]{\\rule{0pt}{1.2em}}}";
+
+fn latex(entries: &[Entry], answer_key: bool) -> String {
+    let heading = if answer_key { "Answer Key" } else { "Test" };
+
+    let mut out = String::new();
+    writeln!(out, "{LATEX_PREAMBLE}").unwrap();
+    writeln!(out, "\\begin{{document}}\n\\section*{{{heading}}}").unwrap();
+
+    for entry in entries {
+        let question = &entry.question;
+        writeln!(out, "\n\\subsection*{{{}}}", escape_latex(&title(question))).unwrap();
+        if let Some(hint) = &question.hint {
+            writeln!(out, "\\textit{{{}}}\n", escape_latex(hint)).unwrap();
+        }
+
+        if answer_key {
+            writeln!(
+                out,
+                "\\begin{{tabular}}{{|l|p{{0.75\\textwidth}}|}}\n\\hline"
+            )
+            .unwrap();
+            for (label, value) in answer_lines(entry) {
+                writeln!(
+                    out,
+                    "{label} & \\texttt{{{}}} \\\\ \\hline",
+                    escape_latex(&value)
+                )
+                .unwrap();
+            }
+            writeln!(out, "\\end{{tabular}}\n").unwrap();
+        } else {
+            for row in rows(&cells(&question.ciphertext, question.r#type)) {
+                let symbols: Vec<_> = row
+                    .iter()
+                    .map(|cell| match cell {
+                        Cell::Symbol { text, .. } => format!("\\texttt{{{}}}", escape_latex(text)),
+                        Cell::Gap => String::new(),
+                    })
+                    .collect();
+                let boxes: Vec<_> = row
+                    .iter()
+                    .map(|cell| match cell {
+                        Cell::Symbol { answer: true, .. } => "\\answerbox",
+                        _ => "",
+                    })
+                    .collect();
+
+                writeln!(out, "\\begin{{tabular}}{{*{{{}}}{{c}}}}", row.len()).unwrap();
+                writeln!(out, "{} \\\\", symbols.join(" & ")).unwrap();
+                writeln!(out, "{} \\\\", boxes.join(" & ")).unwrap();
+                writeln!(out, "\\end{{tabular}}\n").unwrap();
+            }
+
+            if let Some(freqs) = frequencies(&question.ciphertext, question.r#type) {
+                let letters: Vec<_> = freqs.iter().map(|(c, _)| c.to_string()).collect();
+                let counts: Vec<_> = freqs.iter().map(|(_, n)| n.to_string()).collect();
+                writeln!(
+                    out,
+                    "\\begin{{tabular}}{{|l|*{{{}}}{{c|}}}}\n\\hline",
+                    freqs.len()
+                )
+                .unwrap();
+                writeln!(out, " & {} \\\\ \\hline", letters.join(" & ")).unwrap();
+                writeln!(out, "Frequency & {} \\\\ \\hline", counts.join(" & ")).unwrap();
+                writeln!(out, "Replacement{} \\\\ \\hline", " & ".repeat(freqs.len())).unwrap();
+                writeln!(out, "\\end{{tabular}}\n").unwrap();
+            }
+        }
+
+        if let Some(table) = morbit_table(entry, answer_key) {
+            let digits: Vec<_> = table.iter().map(|(d, _)| d.to_string()).collect();
+            let pairs: Vec<_> = table
+                .iter()
+                .map(|(_, p)| format!("\\texttt{{{}}}", escape_latex(p)))
+                .collect();
+            writeln!(out, "\\begin{{tabular}}{{|*{{9}}{{c|}}}}\n\\hline").unwrap();
+            writeln!(out, "{} \\\\ \\hline", digits.join(" & ")).unwrap();
+            writeln!(out, "{} \\\\ \\hline", pairs.join(" & ")).unwrap();
+            writeln!(out, "\\end{{tabular}}\n").unwrap();
+        }
+    }
+
+    writeln!(out, "\\end{{document}}").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(r#type: Type, ciphertext: &str, key: Key) -> Entry {
+        Entry {
+            question: Question {
+                number: 1,
+                token: Token::generate(),
+                r#type,
+                ciphertext: ciphertext.into(),
+                points: 250,
+                hint: Some("Starts with \"HELLO\"".into()),
+            },
+            plaintext: "Hello world".into(),
+            key,
+        }
+    }
+
+    #[test]
+    fn test_cells() {
+        let symbol = |s: &str| Cell::Symbol {
+            text: s.into(),
+            answer: true,
+        };
+
+        assert_eq!(
+            cells("AB  C!", Type::Aristocrat),
+            vec![
+                symbol("A"),
+                symbol("B"),
+                Cell::Gap,
+                symbol("C"),
+                Cell::Symbol {
+                    text: "!".into(),
+                    answer: false
+                }
+            ]
+        );
+        assert_eq!(
+            cells("23 24 / 44", Type::Polybius),
+            vec![symbol("23"), symbol("24"), Cell::Gap, symbol("44")]
+        );
+    }
+
+    #[test]
+    fn test_rows() {
+        let cells = cells(&"ABCDE ".repeat(10), Type::Aristocrat);
+        let rows = rows(&cells);
+
+        assert!(rows.iter().all(|r| r.len() <= GRID_WIDTH));
+        assert!(rows.iter().all(|r| r[0] != Cell::Gap));
+    }
+
+    #[test]
+    fn test_html() {
+        let entries = [entry(Type::Aristocrat, "KVOOR DRUOW", Key::default())];
+        let out = render(&entries, ExportFormat::Html, false);
+
+        assert!(out.contains("<td>K</td>"));
+        assert!(out.contains("Starts with &quot;HELLO&quot;"));
+        assert!(out.contains("<th>Replacement</th>"));
+        assert!(!out.contains("Hello world"));
+    }
+
+    #[test]
+    fn test_latex_answer_key() {
+        let key = Key {
+            keyword: Some("morsecode".into()),
+            morbit: Some(vec!["--".into(); 9]),
+            ..Key::default()
+        };
+        let entries = [entry(Type::Morbit, "3237", key)];
+        let out = render(&entries, ExportFormat::Latex, true);
+
+        assert!(out.contains("Plaintext & \\texttt{Hello world}"));
+        assert!(out.contains("Key & \\texttt{morsecode}"));
+        assert!(out.contains("\\texttt{-{}-{}}"));
+    }
+}
@@ -0,0 +1,208 @@
+//! This module defines how ciphertexts are formatted after encryption.
+
+use super::cryptogram::Type;
+use itertools::Itertools;
+
+/// The case of the letters in the ciphertext.
+#[derive(GraphQLEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Case {
+    /// Uppercase every letter.
+    Upper,
+    /// Lowercase every letter.
+    Lower,
+    /// Keep the case produced by the cipher.
+    Preserve,
+}
+
+/// Options for formatting a ciphertext.
+///
+/// Every option is optional. Unset options use the default for the [`Type`] of the cipher.
+#[derive(GraphQLInputObject, Debug, Default, Clone)]
+pub struct Format {
+    /// Split the ciphertext into groups of this many characters, ignoring whitespace.
+    /// Defaults to 5 for patristocrats and no grouping otherwise. Values less than 1 disable
+    /// grouping.
+    ///
+    /// Only applies to ciphers whose ciphertext is made of letters, since regrouping would merge
+    /// the symbols of the others.
+    pub group_size: Option<i32>,
+    /// The case of the letters. Defaults to `UPPER`.
+    pub case: Option<Case>,
+    /// Keep punctuation from the plaintext. Defaults to true.
+    ///
+    /// Only applies to ciphers whose ciphertext is made of letters.
+    pub keep_punctuation: Option<bool>,
+    /// Keep digits from the plaintext. Defaults to true.
+    ///
+    /// Only applies to ciphers whose ciphertext is made of letters.
+    pub keep_digits: Option<bool>,
+    /// Wrap the ciphertext into lines of at most this many characters. Defaults to no wrapping.
+    /// Values less than 1 disable wrapping.
+    pub line_width: Option<i32>,
+}
+
+impl Format {
+    /// Format `ciphertext`, which was encrypted with `r#type`.
+    pub(crate) fn apply(&self, ciphertext: &str, r#type: Type) -> String {
+        use Type::{Patristocrat, PatristocratK1, PatristocratK2};
+
+        let mut out: String = if r#type.ciphertext_is_letters() {
+            let keep_punctuation = self.keep_punctuation.unwrap_or(true);
+            let keep_digits = self.keep_digits.unwrap_or(true);
+
+            ciphertext
+                .chars()
+                .filter(|c| {
+                    if c.is_ascii_digit() {
+                        keep_digits
+                    } else if c.is_alphanumeric() || c.is_whitespace() {
+                        true
+                    } else {
+                        keep_punctuation
+                    }
+                })
+                .collect()
+        } else {
+            ciphertext.to_string()
+        };
+
+        let group_size = match (self.group_size, r#type) {
+            _ if !r#type.ciphertext_is_letters() => 0,
+            (Some(n), _) => n,
+            (None, Patristocrat | PatristocratK1 | PatristocratK2) => 5,
+            (None, _) => 0,
+        };
+        if group_size > 0 {
+            out = group(&out, group_size as usize);
+        }
+
+        if let Some(width) = self.line_width.filter(|&w| w > 0) {
+            out = wrap(&out, width as usize);
+        }
+
+        match self.case.unwrap_or(Case::Upper) {
+            Case::Upper => out.to_uppercase(),
+            Case::Lower => out.to_lowercase(),
+            Case::Preserve => out,
+        }
+    }
+}
+
+/// Remove whitespace from `s`, then split it into space separated groups of `size` characters.
+fn group(s: &str, size: usize) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .chunks(size)
+        .into_iter()
+        .map(|chunk| chunk.collect::<String>())
+        .join(" ")
+}
+
+/// Break `s` into lines of at most `width` characters.
+///
+/// Lines are broken at spaces. Words longer than `width` are split across lines.
+fn wrap(s: &str, width: usize) -> String {
+    let mut out = String::with_capacity(s.len() + s.len() / width);
+    let mut line_len = 0;
+    let mut line_started = false;
+
+    let chunks = s.split(' ').flat_map(|word| {
+        let chars: Vec<_> = word.chars().collect();
+        if chars.is_empty() {
+            vec![String::new()]
+        } else {
+            chars.chunks(width).map(|c| c.iter().collect()).collect()
+        }
+    });
+
+    for word in chunks {
+        let len = word.chars().count();
+        if line_started && line_len + 1 + len > width {
+            out.push('\n');
+            line_len = 0;
+            line_started = false;
+        }
+
+        if line_started {
+            out.push(' ');
+            line_len += 1;
+        } else if word.is_empty() {
+            // don't start a line with a space
+            continue;
+        }
+
+        out.push_str(&word);
+        line_len += len;
+        line_started = true;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_default() {
+        let format = Format::default();
+
+        assert_eq!(
+            format.apply("Hello, world!", Type::Aristocrat),
+            "HELLO, WORLD!"
+        );
+        assert_eq!(
+            format.apply("helloworld!", Type::Patristocrat),
+            "HELLO WORLD !"
+        );
+    }
+
+    #[test]
+    fn test_format_options() {
+        let format = Format {
+            group_size: Some(3),
+            case: Some(Case::Lower),
+            keep_punctuation: Some(false),
+            keep_digits: Some(false),
+            line_width: Some(8),
+        };
+
+        assert_eq!(
+            format.apply("Hello, World 42!", Type::Caesar),
+            "hel low\norl d"
+        );
+    }
+
+    #[test]
+    fn test_format_symbols_kept() {
+        let format = Format {
+            keep_punctuation: Some(false),
+            keep_digits: Some(false),
+            ..Format::default()
+        };
+
+        assert_eq!(format.apply("23 24 / 44", Type::Polybius), "23 24 / 44");
+    }
+
+    #[test]
+    fn test_format_symbols_not_grouped() {
+        let format = Format {
+            group_size: Some(3),
+            ..Format::default()
+        };
+
+        assert_eq!(format.apply("23 24 / 44", Type::Polybius), "23 24 / 44");
+        assert_eq!(format.apply("23 24 / 44", Type::Aristocrat), "232 4/4 4");
+    }
+
+    #[test]
+    fn test_group() {
+        assert_eq!(group("abc def gh", 4), "abcd efgh");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("aaa bb c dddddddd", 6), "aaa bb\nc\ndddddd\ndd");
+        assert_eq!(wrap(".  ..  ...", 5), ".  ..\n...");
+    }
+}
@@ -0,0 +1,149 @@
+//! This module grades answers to cryptograms, following the Science Olympiad Codebusters rules.
+//!
+//! An answer is compared to the plaintext letter by letter. Spacing, punctuation and case are
+//! ignored, as are letters that the cipher merges, such as "j" and "i" in a Polybius square. The
+//! first [`FREE_ERRORS`] errors are free, and each error after that costs [`ERROR_PENALTY`]
+//! points. Hints also cost points. The score never goes below 0.
+
+use super::cryptogram::Type;
+
+/// The points for a perfect answer, if not given.
+pub(crate) const DEFAULT_POINTS: i32 = 250;
+
+/// The number of errors allowed before points are deducted.
+pub(crate) const FREE_ERRORS: i32 = 2;
+
+/// The points deducted for each error after the free errors.
+pub(crate) const ERROR_PENALTY: i32 = 100;
+
+/// The result of grading an answer.
+#[derive(GraphQLObject, Debug)]
+pub struct Grade {
+    /// The points earned.
+    pub score: i32,
+    /// The points for a perfect answer.
+    pub max_score: i32,
+    /// The points deducted for hints.
+    pub hint_penalty: i32,
+    /// The number of wrong, missing and extra letters.
+    pub errors: i32,
+    /// The 0-indexed positions of the errors, counting only letters.
+    ///
+    /// Positions past the end of the plaintext are extra letters.
+    pub error_positions: Vec<i32>,
+}
+
+/// Grade `attempt` against `plaintext`, which was encrypted with `r#type`.
+///
+/// A perfect answer scores `points`, less `hint_penalty` for the hints used.
+pub(crate) fn grade(
+    plaintext: &str,
+    attempt: &str,
+    r#type: Type,
+    points: i32,
+    hint_penalty: i32,
+) -> Grade {
+    let alphabet = r#type.alphabet();
+    let letters = |s: &str| -> Vec<_> { s.chars().filter_map(|c| alphabet.index_of(c)).collect() };
+
+    let expected = letters(plaintext);
+    let actual = letters(attempt);
+
+    let error_positions: Vec<_> = (0..expected.len().max(actual.len()))
+        .filter(|&i| expected.get(i) != actual.get(i))
+        .map(|i| i as i32)
+        .collect();
+
+    let errors = error_positions.len() as i32;
+    let score = (points - ERROR_PENALTY * (errors - FREE_ERRORS).max(0) - hint_penalty).max(0);
+
+    Grade {
+        score,
+        max_score: points,
+        hint_penalty,
+        errors,
+        error_positions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PLAINTEXT: &str = "Ask not what your country can do for you.";
+
+    #[test]
+    fn test_grade_perfect() {
+        let res = grade(
+            PLAINTEXT,
+            "ASKNOTWHATYOURCOUNTRYCANDOFORYOU",
+            Type::Patristocrat,
+            250,
+            0,
+        );
+
+        assert_eq!(res.score, 250);
+        assert_eq!(res.errors, 0);
+        assert!(res.error_positions.is_empty());
+    }
+
+    #[test]
+    fn test_grade_free_errors() {
+        let res = grade(
+            PLAINTEXT,
+            "ask nbt what your cuuntry can do for you",
+            Type::Aristocrat,
+            250,
+            0,
+        );
+
+        assert_eq!(res.score, 250);
+        assert_eq!(res.error_positions, vec![4, 15]);
+    }
+
+    #[test]
+    fn test_grade_penalty() {
+        let res = grade(
+            PLAINTEXT,
+            "ask not what your country",
+            Type::Aristocrat,
+            250,
+            0,
+        );
+
+        // "candoforyou" is missing
+        assert_eq!(res.errors, 11);
+        assert_eq!(res.score, 0);
+
+        let res = grade(
+            PLAINTEXT,
+            "ask not what your country can do for yoxxx",
+            Type::Aristocrat,
+            250,
+            0,
+        );
+        assert_eq!(res.score, 150);
+    }
+
+    #[test]
+    fn test_grade_hints() {
+        let res = grade(PLAINTEXT, PLAINTEXT, Type::Aristocrat, 250, 100);
+
+        assert_eq!(res.score, 150);
+        assert_eq!(res.hint_penalty, 100);
+    }
+
+    #[test]
+    fn test_grade_extra_letters() {
+        let res = grade("abc", "abcde", Type::Aristocrat, 250, 0);
+
+        assert_eq!(res.error_positions, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_grade_merged_letters() {
+        let res = grade("Jig", "iig", Type::Polybius, 250, 0);
+
+        assert_eq!(res.errors, 0);
+    }
+}
@@ -0,0 +1,213 @@
+//! This module gives hints for stored cryptograms.
+//!
+//! Hints are revealed in a fixed order, so that asking for the same kind of hint again always
+//! reveals something new. Each hint costs points when the answer is graded, see
+//! [`HintKind::penalty`].
+
+use super::cryptogram::Type;
+use std::fmt;
+
+/// The kind of a hint.
+#[derive(GraphQLEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HintKind {
+    /// Reveal a plaintext letter. The most common letters are revealed first.
+    Letter,
+    /// Reveal a whole plaintext word. The longest words are revealed first.
+    Word,
+    /// Reveal the next letter of the key.
+    KeywordLetter,
+}
+
+impl HintKind {
+    /// The points deducted from the score for each hint of this kind.
+    pub(crate) fn penalty(self) -> i32 {
+        match self {
+            HintKind::Letter | HintKind::KeywordLetter => 50,
+            HintKind::Word => 100,
+        }
+    }
+}
+
+/// Hint kinds are stored in the database by variant name.
+impl fmt::Display for HintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A hint for a cryptogram.
+#[derive(GraphQLObject, Debug)]
+pub struct Hint {
+    pub kind: HintKind,
+    /// Where the hint is, 0-indexed.
+    ///
+    /// For letter hints, this is the first occurrence of the letter, counting only letters. For
+    /// word hints, this is the word, and for keyword hints, this is the letter of the key.
+    pub position: i32,
+    /// The revealed plaintext, uppercased. For keyword hints, this is the key letter.
+    pub plaintext: String,
+    /// The ciphertext letter that decrypts to the revealed letter.
+    ///
+    /// Only given for letter hints on monoalphabetic substitution ciphers.
+    pub ciphertext: Option<String>,
+    /// The points this hint costs.
+    pub penalty: i32,
+}
+
+/// Stored data about a cryptogram that hints are taken from.
+pub(crate) struct Stored<'a> {
+    pub plaintext: &'a str,
+    pub ciphertext: Option<&'a str>,
+    pub key: Option<&'a str>,
+    /// The ciphertext letter of each letter of the alphabet, for substitution ciphers.
+    pub mapping: Option<&'a str>,
+    pub r#type: Type,
+}
+
+/// Give the next hint of `kind`, skipping hints at `used` positions.
+///
+/// Returns `None` if there is nothing left to reveal.
+pub(crate) fn hint(kind: HintKind, stored: &Stored, used: &[i32]) -> Option<Hint> {
+    let (position, plaintext, ciphertext) = match kind {
+        HintKind::Letter => letter(stored, used)?,
+        HintKind::Word => word(stored.plaintext, used)?,
+        HintKind::KeywordLetter => {
+            let (i, c) = stored
+                .key?
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .enumerate()
+                .find(|&(i, _)| !used.contains(&(i as i32)))?;
+            (i, c.to_uppercase().to_string(), None)
+        }
+    };
+
+    Some(Hint {
+        kind,
+        position: position as i32,
+        plaintext,
+        ciphertext,
+        penalty: kind.penalty(),
+    })
+}
+
+/// Reveal the most common plaintext letter that has not been revealed.
+fn letter(stored: &Stored, used: &[i32]) -> Option<(usize, String, Option<String>)> {
+    let alphabet = stored.r#type.alphabet();
+    let letters: Vec<_> = stored
+        .plaintext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+
+    let mut counts = vec![0; alphabet.len()];
+    for &l in &letters {
+        counts[l] += 1;
+    }
+
+    // letters are revealed by the position of their first occurrence
+    let (position, &l) = letters
+        .iter()
+        .enumerate()
+        .filter(|&(i, &l)| letters.iter().position(|&m| m == l) == Some(i))
+        .filter(|&(i, _)| !used.contains(&(i as i32)))
+        .max_by_key(|&(i, &l)| (counts[l], std::cmp::Reverse(i)))?;
+
+    // in a monoalphabetic substitution, the nth ciphertext letter encrypts the nth plaintext letter,
+    // which is only needed for cryptograms stored without their mapping
+    let ciphertext = if stored.r#type.is_monoalphabetic() {
+        match stored.mapping {
+            Some(mapping) => mapping.chars().nth(l),
+            None => stored.ciphertext.and_then(|s| {
+                s.chars()
+                    .filter(|&c| alphabet.index_of(c).is_some())
+                    .nth(position)
+            }),
+        }
+        .map(|c| c.to_uppercase().to_string())
+    } else {
+        None
+    };
+
+    let plaintext = alphabet.symbol(l).to_uppercase().to_string();
+    Some((position, plaintext, ciphertext))
+}
+
+/// Reveal the longest plaintext word that has not been revealed.
+fn word(plaintext: &str, used: &[i32]) -> Option<(usize, String, Option<String>)> {
+    let (position, word) = plaintext
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+        .enumerate()
+        .filter(|&(i, w)| !w.is_empty() && !used.contains(&(i as i32)))
+        .max_by_key(|&(i, w)| (w.chars().count(), std::cmp::Reverse(i)))?;
+
+    Some((position, word.to_uppercase(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static STORED: Stored = Stored {
+        plaintext: "Ask not what your country can do for you.",
+        ciphertext: Some("ZHP MLG DSZG BLFI XLFMGIB XZM WL ULI BLF."),
+        key: Some("zyx"),
+        mapping: None,
+        r#type: Type::Aristocrat,
+    };
+
+    #[test]
+    fn test_letter() {
+        let first = hint(HintKind::Letter, &STORED, &[]).unwrap();
+        assert_eq!(first.plaintext, "O");
+        assert_eq!(first.ciphertext.as_deref(), Some("L"));
+        assert_eq!(first.penalty, 50);
+
+        let second = hint(HintKind::Letter, &STORED, &[first.position]).unwrap();
+        assert_eq!(second.plaintext, "A");
+        assert_eq!(second.ciphertext.as_deref(), Some("Z"));
+    }
+
+    #[test]
+    fn test_word() {
+        let first = hint(HintKind::Word, &STORED, &[]).unwrap();
+        assert_eq!((first.position, first.plaintext.as_str()), (4, "COUNTRY"));
+
+        let second = hint(HintKind::Word, &STORED, &[4]).unwrap();
+        assert_eq!((second.position, second.plaintext.as_str()), (2, "WHAT"));
+    }
+
+    #[test]
+    fn test_keyword_letter() {
+        let res = hint(HintKind::KeywordLetter, &STORED, &[0]).unwrap();
+        assert_eq!(res.plaintext, "Y");
+
+        assert!(hint(HintKind::KeywordLetter, &STORED, &[0, 1, 2]).is_none());
+    }
+
+    #[test]
+    fn test_letter_not_monoalphabetic() {
+        let stored = Stored {
+            r#type: Type::Hill,
+            ..STORED
+        };
+
+        assert!(hint(HintKind::Letter, &stored, &[])
+            .unwrap()
+            .ciphertext
+            .is_none());
+    }
+
+    #[test]
+    fn test_letter_mapping() {
+        let stored = Stored {
+            mapping: Some("zyxwvutsrqponmlkjihgfedcba"),
+            ciphertext: None,
+            ..STORED
+        };
+
+        let res = hint(HintKind::Letter, &stored, &[]).unwrap();
+        assert_eq!(res.ciphertext.as_deref(), Some("L"));
+    }
+}
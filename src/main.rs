@@ -1,11 +1,30 @@
+use cryptograms::config::{self, Config};
 use std::env;
+use std::process::ExitCode;
 
-fn main() {
-    env_logger::init();
+fn main() -> ExitCode {
+    let config = match Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    config.init_logger();
+    config::init(config);
 
-    if env::args().any(|arg| arg == "print") {
+    let args: Vec<_> = env::args().skip(1).collect();
+
+    if let Some(code) = cryptograms::cli::run(&args) {
+        return code;
+    }
+
+    if args.iter().any(|arg| arg == "print") {
         cryptograms::print_schema();
-    } else {
-        cryptograms::make_server();
+    } else if let Err(e) = cryptograms::make_server() {
+        eprintln!("error: {e}");
+        return ExitCode::FAILURE;
     }
+
+    ExitCode::SUCCESS
 }
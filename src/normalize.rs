@@ -0,0 +1,177 @@
+//! This module defines how plaintexts are cleaned up before encryption.
+//!
+//! Quotes are full of typographic punctuation, such as curly quotes and em dashes, and of accented
+//! names. Ciphers only know about the letters in their alphabet and leave everything else
+//! unchanged, so these are normalized first.
+
+use std::iter;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Options for normalizing a plaintext.
+///
+/// Every option is optional. Characters that are not normalized are kept as they are.
+#[derive(GraphQLInputObject, Debug, Default, Clone)]
+pub struct Normalization {
+    /// Replace typographic punctuation, such as curly quotes, dashes and ellipses, with ASCII.
+    /// Defaults to true.
+    pub ascii_punctuation: Option<bool>,
+    /// Replace accented letters with unaccented letters, such as "é" with "e". Defaults to true.
+    pub fold_accents: Option<bool>,
+}
+
+impl Normalization {
+    /// Normalize `s`.
+    pub(crate) fn apply(&self, s: &str) -> String {
+        let ascii_punctuation = self.ascii_punctuation.unwrap_or(true);
+        let fold_accents = self.fold_accents.unwrap_or(true);
+
+        let mut out = String::with_capacity(s.len());
+        for c in s.chars() {
+            if let Some(r) = ascii_punctuation.then(|| punctuation(c)).flatten() {
+                out.push_str(r);
+            } else if let Some(r) = fold_accents.then(|| fold_accent(c)).flatten() {
+                out.push_str(&r);
+            } else {
+                out.push(c);
+            }
+        }
+
+        out
+    }
+}
+
+/// Returns the ASCII replacement for typographic punctuation.
+fn punctuation(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => "'",
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' | '«' | '»' => "\"",
+        '\u{2010}'..='\u{2013}' | '\u{2212}' => "-",
+        '\u{2014}' | '\u{2015}' => "--",
+        '\u{2026}' => "...",
+        '\u{00A0}' | '\u{2002}'..='\u{200A}' | '\u{202F}' => " ",
+        _ => return None,
+    })
+}
+
+/// Returns the unaccented replacement for an accented letter.
+///
+/// Letters are decomposed into a base letter and combining marks, such as "é" into "e" and an acute
+/// accent, and the marks are dropped. Combining marks on their own are dropped too. Letters that
+/// don't decompose, such as "ß" and "ø", are looked up instead.
+fn fold_accent(c: char) -> Option<String> {
+    if let Some(folded) = fold_letter(c) {
+        return Some(folded.into());
+    }
+
+    let mut marks = false;
+    let folded: String = iter::once(c)
+        .nfd()
+        .filter(|&d| {
+            let mark = is_combining_mark(d);
+            marks |= mark;
+            !mark
+        })
+        .collect();
+
+    marks.then_some(folded)
+}
+
+/// Returns the replacement for a letter that has no canonical decomposition.
+fn fold_letter(c: char) -> Option<&'static str> {
+    Some(match c {
+        'æ' => "ae",
+        'Æ' => "AE",
+        'đ' => "d",
+        'Đ' => "D",
+        'ı' => "i",
+        'ł' => "l",
+        'Ł' => "L",
+        'ø' => "o",
+        'Ø' => "O",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ß' => "ss",
+        'ẞ' => "SS",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smart_quotes() {
+        let quote = "“Be yourself; everyone else is already taken.” — Oscar Wilde";
+
+        assert_eq!(
+            Normalization::default().apply(quote),
+            "\"Be yourself; everyone else is already taken.\" -- Oscar Wilde"
+        );
+    }
+
+    #[test]
+    fn test_apostrophes_and_ellipsis() {
+        let quote = "It’s not that I’m so smart, it’s just that I stay with problems longer…";
+
+        assert_eq!(
+            Normalization::default().apply(quote),
+            "It's not that I'm so smart, it's just that I stay with problems longer..."
+        );
+    }
+
+    #[test]
+    fn test_fold_accents() {
+        let quote = "Gabriel García Márquez wrote “Cien años de soledad.”";
+
+        assert_eq!(
+            Normalization::default().apply(quote),
+            "Gabriel Garcia Marquez wrote \"Cien anos de soledad.\""
+        );
+    }
+
+    #[test]
+    fn test_fold_decomposed_accents() {
+        let quote = "Ŕ ḿ ǘ ș ț ų ẁ ỳ ḍ ǵ Ḱ Ĺ ǒ";
+
+        assert_eq!(
+            Normalization::default().apply(quote),
+            "R m u s t u w y d g K L o"
+        );
+        // combining marks are dropped, even after an unaccented letter
+        assert_eq!(Normalization::default().apply("Cafe\u{301}"), "Cafe");
+    }
+
+    #[test]
+    fn test_fold_undecomposable_letters() {
+        assert_eq!(
+            Normalization::default().apply("Straße, Ærø, œuvre, Łódź, Đorđe, ı"),
+            "Strasse, AEro, oeuvre, Lodz, Dorde, i"
+        );
+        // letters without marks are kept
+        assert_eq!(Normalization::default().apply("Αθήνα 한국"), "Αθηνα 한국");
+    }
+
+    #[test]
+    fn test_options_disabled() {
+        let quote = "Pelé’s “Éxito”";
+        let normalization = Normalization {
+            ascii_punctuation: Some(false),
+            fold_accents: Some(false),
+        };
+
+        assert_eq!(normalization.apply(quote), quote);
+    }
+
+    #[test]
+    fn test_keeps_accents() {
+        let quote = "Señor Müller’s café";
+        let normalization = Normalization {
+            ascii_punctuation: None,
+            fold_accents: Some(false),
+        };
+
+        assert_eq!(normalization.apply(quote), "Señor Müller's café");
+    }
+}
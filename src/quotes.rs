@@ -1,58 +1,424 @@
-use crate::cryptogram::Length;
+//! This module defines the corpus of quotes that plaintexts and quote keys are picked from.
+//!
+//! Quotes are kept in the store, so admins can add, edit and disable them through the API without
+//! restarting the server. The JSON file in `QUOTES_FILE` seeds the store when it has no quotes yet,
+//! and is read directly by the command line, which works without a database.
+//!
+//! Requests can name a [`QuoteHistory`], such as a practice session, so the same quotes are not
+//! served again until every quote that could be picked has been.
+
+use crate::auth::Role;
+use crate::ciphers::{CipherError, CipherResult, ErrorKind};
+use crate::cryptogram::{Cryptogram, Length};
+use crate::store::{CryptogramStore, StoreResult};
 use juniper::serde::Deserialize;
-use lazy_static::lazy_static;
 use rand::prelude::*;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock};
 
-lazy_static! {
-    static ref QUOTES: Vec<SerQuote> = {
-        let quotes_file = std::env::var("QUOTES_FILE");
-        let file_contents = std::fs::read_to_string(
-            quotes_file
-                .as_ref()
-                .expect("Environment variable QUOTES_FILE must be set."),
-        )
-        .unwrap();
-
-        log::info!("Loading quotes from {:?}", quotes_file.unwrap());
-        serde_json::from_str(&file_contents).unwrap()
-    };
+/// A quote of the corpus.
+#[derive(GraphQLObject, Debug, Clone, PartialEq, Eq)]
+pub struct Quote {
+    /// The ID to edit the quote.
+    pub id: i32,
+    /// The text of the quote.
+    pub text: String,
+    /// Who said or wrote the quote.
+    pub author: Option<String>,
+    /// The genre, such as "Humor" or "Science".
+    pub genre: Option<String>,
+    /// Where the quote is from, such as a book or a speech.
+    pub source: Option<String>,
+    /// The language of the quote, such as "en".
+    pub language: Option<String>,
+    /// Labels for picking quotes, in lowercase.
+    pub tags: Vec<String>,
+    /// Whether the quote is picked for new cryptograms.
+    pub enabled: bool,
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(crate = "juniper::serde")]
-struct SerQuote {
-    quote: String,
-    author: String,
+impl Quote {
+    /// An enabled quote with ID `id` and the fields of `input`.
+    pub(crate) fn new(id: i32, input: QuoteInput) -> Self {
+        Self {
+            id,
+            text: input.text,
+            author: input.author,
+            genre: input.genre,
+            source: input.source,
+            language: input.language,
+            tags: input.tags.unwrap_or_default(),
+            enabled: true,
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct Quote {
+/// The fields of a quote to add or edit.
+///
+/// Quotes files are JSON lists of these, where `text` can also be called `quote`.
+#[derive(GraphQLInputObject, Deserialize, Debug, Default, Clone)]
+#[serde(crate = "juniper::serde", default)]
+pub struct QuoteInput {
+    /// The text of the quote.
+    #[serde(alias = "quote")]
     pub text: String,
+    /// Who said or wrote the quote.
     pub author: Option<String>,
-    pub length: usize,
+    /// The genre, such as "Humor" or "Science".
+    pub genre: Option<String>,
+    /// Where the quote is from, such as a book or a speech.
+    pub source: Option<String>,
+    /// The language of the quote, such as "en".
+    pub language: Option<String>,
+    /// Labels for picking quotes. They are lowercased, and duplicates are dropped.
+    pub tags: Option<Vec<String>>,
 }
 
-impl Quote {
-    pub fn new(text: String, author: Option<String>) -> Self {
-        let length = text.len();
-        Self {
-            text,
-            author,
-            length,
+impl QuoteInput {
+    /// Trim the text and clean up the tags, or fail with an `INVALID_ARGUMENT` error if there is
+    /// no text.
+    pub(crate) fn normalized(self) -> CipherResult<Self> {
+        let text = self.text.trim();
+        if text.is_empty() {
+            return Err(CipherError::new(
+                ErrorKind::InvalidArgument,
+                "A quote can't be empty".into(),
+            )
+            .with_field("text"));
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags.iter().flatten() {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        Ok(Self {
+            text: text.into(),
+            tags: Some(tags),
+            ..self
+        })
+    }
+
+    /// The tags, which are none if they were not given.
+    pub(crate) fn tags(&self) -> &[String] {
+        self.tags.as_deref().unwrap_or_default()
+    }
+}
+
+/// Constraints on the quote picked for a cryptogram. Every constraint that is given must hold.
+#[derive(GraphQLInputObject, Deserialize, Debug, Default, Clone)]
+#[serde(crate = "juniper::serde", rename_all = "camelCase", default)]
+pub struct QuoteFilter {
+    /// Only quotes by this author, ignoring case.
+    pub author: Option<String>,
+    /// Only quotes of this genre, ignoring case.
+    pub genre: Option<String>,
+    /// Only quotes with every one of these tags.
+    pub tags: Option<Vec<String>>,
+    /// Only quotes that contain every letter of this string, ignoring case. Other characters are
+    /// ignored.
+    pub contains_letters: Option<String>,
+    /// Only quotes with at least this many different letters, which makes aristocrats easier to
+    /// solve.
+    pub min_unique_letters: Option<i32>,
+}
+
+impl QuoteFilter {
+    /// Whether `quote` meets every constraint.
+    pub(crate) fn matches(&self, quote: &Quote) -> bool {
+        let same = |wanted: &Option<String>, value: &Option<String>| match (wanted, value) {
+            (None, _) => true,
+            (Some(wanted), Some(value)) => {
+                wanted.trim().to_lowercase() == value.trim().to_lowercase()
+            }
+            (Some(_), None) => false,
+        };
+        if !same(&self.author, &quote.author) || !same(&self.genre, &quote.genre) {
+            return false;
+        }
+
+        if let Some(tags) = &self.tags {
+            if !tags
+                .iter()
+                .all(|tag| quote.tags.contains(&tag.trim().to_lowercase()))
+            {
+                return false;
+            }
+        }
+
+        let letters: HashSet<char> = quote
+            .text
+            .chars()
+            .filter(|c| c.is_alphabetic())
+            .flat_map(char::to_lowercase)
+            .collect();
+        if let Some(wanted) = &self.contains_letters {
+            if !wanted
+                .chars()
+                .filter(|c| c.is_alphabetic())
+                .flat_map(char::to_lowercase)
+                .all(|c| letters.contains(&c))
+            {
+                return false;
+            }
+        }
+
+        match self.min_unique_letters {
+            Some(min) => letters.len() as i64 >= i64::from(min),
+            None => true,
+        }
+    }
+}
+
+/// What a quote history belongs to.
+#[derive(GraphQLEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HistoryScope {
+    /// A practice session.
+    Session,
+    /// A student or a team.
+    User,
+    /// A test, or a series of tests that should not share quotes.
+    Test,
+}
+
+impl HistoryScope {
+    /// The least role that can use or reset a history of this scope. Students choose the IDs of
+    /// their own sessions, but user and test histories are managed by proctors.
+    pub(crate) fn role(self) -> Role {
+        match self {
+            HistoryScope::Session => Role::Student,
+            HistoryScope::User | HistoryScope::Test => Role::Proctor,
+        }
+    }
+}
+
+/// A history of the quotes served, so they are not picked again until every quote that could be
+/// picked has been.
+#[derive(GraphQLInputObject, Debug, Clone)]
+pub struct QuoteHistory {
+    /// What the history belongs to.
+    pub scope: HistoryScope,
+    /// The ID of the session, user or test, chosen by the client.
+    pub id: String,
+}
+
+impl QuoteHistory {
+    /// The key the history is stored under, such as `session:abc`, or an `INVALID_ARGUMENT` error
+    /// if the ID is empty.
+    pub(crate) fn key(&self) -> CipherResult<String> {
+        let id = self.id.trim();
+        if id.is_empty() {
+            return Err(CipherError::new(
+                ErrorKind::InvalidArgument,
+                "A quote history needs an ID".into(),
+            )
+            .with_field("history.id"));
+        }
+
+        let scope = match self.scope {
+            HistoryScope::Session => "session",
+            HistoryScope::User => "user",
+            HistoryScope::Test => "test",
+        };
+        Ok(format!("{scope}:{id}"))
+    }
+}
+
+/// The quotes served to a [`QuoteHistory`], as loaded from the store.
+#[derive(Debug)]
+pub(crate) struct History {
+    key: String,
+    served: HashSet<i32>,
+}
+
+impl History {
+    /// Load the quotes served to `history` from `store`.
+    pub(crate) fn load(store: &dyn CryptogramStore, history: &QuoteHistory) -> CipherResult<Self> {
+        let key = history.key()?;
+        let served = store.served_quotes(&key)?.into_iter().collect();
+
+        Ok(Self { key, served })
+    }
+
+    /// The IDs of the served quotes to skip when picking a quote with `seed`.
+    ///
+    /// The same seed always gives the same cryptogram, so nothing is skipped when a seed is given.
+    /// The quote picked is still recorded.
+    pub(crate) fn skipped(&self, seed: Option<i32>) -> Option<&HashSet<i32>> {
+        seed.is_none().then_some(&self.served)
+    }
+
+    /// Record in `store` that the quote of `cryptogram` was served, if it is from `quotes`.
+    ///
+    /// A quote that was already served means every quote it could be picked with was too, so the
+    /// served quotes of its length that match `filter`, the filter it was picked with, are
+    /// forgotten first, and the history starts over for them. Served quotes that `filter` leaves
+    /// out are kept.
+    pub(crate) fn record(
+        &mut self,
+        store: &dyn CryptogramStore,
+        quotes: &[Quote],
+        cryptogram: &Cryptogram,
+        filter: Option<&QuoteFilter>,
+    ) -> StoreResult<()> {
+        let id = match cryptogram.quote_id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        if self.served.contains(&id) {
+            let pickable: Vec<i32> = quotes
+                .iter()
+                .filter(|quote| has_length(quote, cryptogram.length))
+                .filter(|quote| filter.map_or(true, |filter| filter.matches(quote)))
+                .map(|quote| quote.id)
+                .collect();
+            log::debug!(
+                "Starting {:?} over for {:?} quotes",
+                self.key,
+                cryptogram.length
+            );
+
+            store.forget_quotes(&self.key, &pickable)?;
+            for id in &pickable {
+                self.served.remove(id);
+            }
+        }
+
+        store.record_quote(&self.key, id)?;
+        self.served.insert(id);
+
+        Ok(())
+    }
+}
+
+/// The enabled quotes, loaded from the store on first use and shared by every request until the
+/// quotes change.
+///
+/// Each server has its own cache, so quotes changed through another server sharing the database
+/// are only seen after a restart.
+#[derive(Debug, Default)]
+pub(crate) struct QuoteCache {
+    quotes: RwLock<Option<Arc<Vec<Quote>>>>,
+}
+
+impl QuoteCache {
+    /// The enabled quotes, loaded from `store` unless they are cached.
+    pub(crate) fn get(&self, store: &dyn CryptogramStore) -> StoreResult<Arc<Vec<Quote>>> {
+        // the cache is only ever replaced whole, so a panic can't leave it half written
+        if let Some(quotes) = &*self.quotes.read().unwrap_or_else(PoisonError::into_inner) {
+            return Ok(Arc::clone(quotes));
+        }
+
+        let mut cached = self.quotes.write().unwrap_or_else(PoisonError::into_inner);
+        // another request may have loaded them while this one waited
+        if let Some(quotes) = &*cached {
+            return Ok(Arc::clone(quotes));
+        }
+
+        let quotes = Arc::new(store.quotes(false)?);
+        *cached = Some(Arc::clone(&quotes));
+
+        Ok(quotes)
+    }
+
+    /// Forget the cached quotes, so they are loaded again. Call this after changing the quotes.
+    pub(crate) fn invalidate(&self) {
+        *self.quotes.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+/// Read the quotes in the JSON file at `path`.
+pub(crate) fn read_file(path: &Path) -> Result<Vec<QuoteInput>, Box<dyn Error>> {
+    let file_contents = std::fs::read_to_string(path)?;
+    let quotes: Vec<QuoteInput> = serde_json::from_str(&file_contents)?;
+
+    quotes
+        .into_iter()
+        .enumerate()
+        .map(|(i, quote)| {
+            quote
+                .normalized()
+                .map_err(|e| format!("Invalid quote {i} in {path:?}: {e}").into())
+        })
+        .collect()
+}
+
+/// Read the quotes in the JSON file at `path` as a corpus, with IDs in the order of the file.
+pub(crate) fn from_file(path: &Path) -> Result<Vec<Quote>, Box<dyn Error>> {
+    log::info!("Loading quotes from {:?}", path);
+
+    Ok(read_file(path)?
+        .into_iter()
+        .zip(1..)
+        .map(|(quote, id)| Quote::new(id, quote))
+        .collect())
+}
+
+/// Add the quotes in the JSON file at `path` to `store`, if `store` has no quotes yet.
+///
+/// Quotes that were added, edited or disabled later are left alone, since the file is only read
+/// once.
+pub(crate) fn seed(store: &dyn CryptogramStore, path: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    if !store.quotes(true)?.is_empty() {
+        return Ok(());
+    }
+
+    match path {
+        Some(path) => {
+            let added = store.insert_quotes(&read_file(path)?)?;
+            log::info!("Seeded {} quotes from {:?}", added.len(), path);
         }
+        None => log::warn!("There are no quotes, and QUOTES_FILE is not set to seed them."),
     }
+
+    Ok(())
+}
+
+/// Returns the text of every one of `quotes`, in order.
+///
+/// This is used as the key material for [`crate::ciphers`] that are keyed by other quotes.
+pub(crate) fn texts(quotes: &[Quote]) -> Vec<&str> {
+    quotes.iter().map(|quote| quote.text.as_str()).collect()
 }
 
-pub fn fetch_quote(length: Length) -> Quote {
-    // TODO: don't select duplicate quotes
+/// Whether `quote` is of the given length.
+fn has_length(quote: &Quote, length: Length) -> bool {
     let len = match length {
         Length::Short => 60,
         Length::Medium => 90,
         Length::Long => 120,
     };
-    let right_length: Vec<_> = QUOTES
+    let n = quote.text.chars().count();
+    len <= n && n < len + 30
+}
+
+/// Select a random quote of the given length from `quotes` that matches `filter`, using `rng`.
+///
+/// Quotes in `used` are never picked. Quotes in `served` are skipped, unless every other matching
+/// quote is in it.
+///
+/// Returns a `NoQuoteAvailable` error if there are no quotes of that length, blaming `quoteFilter`
+/// if there are some but none match it, or if every matching quote is in `used`.
+pub(crate) fn fetch_quote<'a, R>(
+    quotes: &'a [Quote],
+    length: Length,
+    filter: Option<&QuoteFilter>,
+    served: Option<&HashSet<i32>>,
+    used: Option<&HashSet<i32>>,
+    rng: &mut R,
+) -> CipherResult<&'a Quote>
+where
+    R: Rng + ?Sized,
+{
+    let right_length: Vec<_> = quotes
         .iter()
-        .filter(|quote| len <= quote.quote.len() && quote.quote.len() < len + 30)
+        .filter(|quote| has_length(quote, length))
         .collect();
 
     log::debug!(
@@ -61,9 +427,354 @@ pub fn fetch_quote(length: Length) -> Quote {
         right_length.len()
     );
 
-    let quote = right_length.choose(&mut thread_rng()).unwrap();
+    if right_length.is_empty() {
+        return Err(CipherError::new(
+            ErrorKind::NoQuoteAvailable,
+            format!("There are no {length:?} quotes"),
+        ));
+    }
 
-    log::debug!("Selected {:?}", quote.quote);
+    let matching: Vec<_> = match filter {
+        Some(filter) => right_length
+            .iter()
+            .copied()
+            .filter(|quote| filter.matches(quote))
+            .collect(),
+        None => right_length.clone(),
+    };
+    if matching.is_empty() {
+        return Err(CipherError::new(
+            ErrorKind::NoQuoteAvailable,
+            format!(
+                "None of the {} {length:?} quotes match the quote filter",
+                right_length.len()
+            ),
+        )
+        .with_field("quoteFilter"));
+    }
 
-    Quote::new(quote.quote.clone(), Some(quote.author.clone()))
+    let unused: Vec<_> = matching
+        .iter()
+        .copied()
+        .filter(|quote| used.map_or(true, |used| !used.contains(&quote.id)))
+        .collect();
+    let unserved: Vec<_> = match served {
+        Some(served) => unused
+            .iter()
+            .copied()
+            .filter(|quote| !served.contains(&quote.id))
+            .collect(),
+        None => Vec::new(),
+    };
+    let candidates = if unserved.is_empty() {
+        &unused
+    } else {
+        &unserved
+    };
+
+    let quote = candidates.choose(rng).ok_or_else(|| {
+        CipherError::new(
+            ErrorKind::NoQuoteAvailable,
+            format!(
+                "All {} {length:?} quotes that could be picked are already used",
+                matching.len()
+            ),
+        )
+    })?;
+
+    log::debug!("Selected {:?}", quote.text);
+
+    Ok(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized() {
+        let quote = QuoteInput {
+            text: "  Veni, vidi, vici. ".into(),
+            tags: Some(vec![
+                "Latin".into(),
+                " latin".into(),
+                "".into(),
+                "war".into(),
+            ]),
+            ..QuoteInput::default()
+        }
+        .normalized()
+        .unwrap();
+        assert_eq!(quote.text, "Veni, vidi, vici.");
+        assert_eq!(quote.tags(), ["latin", "war"]);
+
+        assert!(QuoteInput::default().normalized().is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let quote = Quote::new(
+            1,
+            QuoteInput {
+                text: "Quick zephyrs blow, vexing daft Jim.".into(),
+                author: Some("Anonymous".into()),
+                genre: Some("Pangram".into()),
+                tags: Some(vec!["letters".into(), "short".into()]),
+                ..QuoteInput::default()
+            },
+        );
+
+        assert!(QuoteFilter::default().matches(&quote));
+        assert!(QuoteFilter {
+            author: Some("anonymous".into()),
+            genre: Some("PANGRAM".into()),
+            tags: Some(vec!["Short".into()]),
+            contains_letters: Some("J, Q, Z".into()),
+            min_unique_letters: Some(26),
+        }
+        .matches(&quote));
+
+        let no_match = [
+            QuoteFilter {
+                author: Some("Jim".into()),
+                ..QuoteFilter::default()
+            },
+            QuoteFilter {
+                genre: Some("Sports".into()),
+                ..QuoteFilter::default()
+            },
+            QuoteFilter {
+                tags: Some(vec!["short".into(), "sports".into()]),
+                ..QuoteFilter::default()
+            },
+            QuoteFilter {
+                contains_letters: Some("é".into()),
+                ..QuoteFilter::default()
+            },
+            QuoteFilter {
+                min_unique_letters: Some(27),
+                ..QuoteFilter::default()
+            },
+        ];
+        for filter in no_match {
+            assert!(!filter.matches(&quote), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn test_fetch_quote_filter() {
+        let quote = |text: &str, genre: &str| {
+            Quote::new(
+                1,
+                QuoteInput {
+                    text: text.repeat(10),
+                    genre: Some(genre.into()),
+                    ..QuoteInput::default()
+                },
+            )
+        };
+        let quotes = [quote("Goal! ", "Sports"), quote("Hmm.. ", "Philosophy")];
+        let rng = &mut StdRng::seed_from_u64(1);
+
+        let filter = QuoteFilter {
+            genre: Some("sports".into()),
+            ..QuoteFilter::default()
+        };
+        for _ in 0..10 {
+            let quote =
+                fetch_quote(&quotes, Length::Short, Some(&filter), None, None, rng).unwrap();
+            assert_eq!(quote.genre.as_deref(), Some("Sports"));
+        }
+
+        let filter = QuoteFilter {
+            genre: Some("Poetry".into()),
+            ..QuoteFilter::default()
+        };
+        let err = fetch_quote(&quotes, Length::Short, Some(&filter), None, None, rng).unwrap_err();
+        assert!(err.to_string().contains("None of the 2 Short quotes"));
+    }
+
+    #[test]
+    fn test_fetch_quote_served() {
+        let quotes: Vec<_> = (1..=3)
+            .map(|id| {
+                Quote::new(
+                    id,
+                    QuoteInput {
+                        text: format!("{id}").repeat(60),
+                        ..QuoteInput::default()
+                    },
+                )
+            })
+            .collect();
+        let rng = &mut StdRng::seed_from_u64(1);
+
+        let served = HashSet::from([1, 3]);
+        for _ in 0..10 {
+            let quote =
+                fetch_quote(&quotes, Length::Short, None, Some(&served), None, rng).unwrap();
+            assert_eq!(quote.id, 2);
+        }
+
+        // every quote was served, so any can be picked
+        let served = HashSet::from([1, 2, 3]);
+        assert!(fetch_quote(&quotes, Length::Short, None, Some(&served), None, rng).is_ok());
+    }
+
+    #[test]
+    fn test_history_key() {
+        let history = QuoteHistory {
+            scope: HistoryScope::Session,
+            id: " abc ".into(),
+        };
+        assert_eq!(history.key().unwrap(), "session:abc");
+
+        let history = QuoteHistory {
+            scope: HistoryScope::User,
+            id: "".into(),
+        };
+        assert!(history.key().is_err());
+    }
+
+    #[test]
+    fn test_history_record() {
+        use crate::cryptogram::Options;
+        use crate::store::MemoryStore;
+
+        let quote = |letter: &str, genre: &str| QuoteInput {
+            text: letter.repeat(60),
+            genre: Some(genre.into()),
+            ..QuoteInput::default()
+        };
+        let store = MemoryStore::default();
+        let quotes = store
+            .insert_quotes(&[
+                quote("a", "Poetry"),
+                quote("b", "Poetry"),
+                quote("c", "History"),
+            ])
+            .unwrap();
+        let key = QuoteHistory {
+            scope: HistoryScope::Session,
+            id: "abc".into(),
+        };
+        let mut history = History::load(&store, &key).unwrap();
+
+        let mut pick = |genre: &str| {
+            let filter = QuoteFilter {
+                genre: Some(genre.into()),
+                ..QuoteFilter::default()
+            };
+            let cryptogram = Cryptogram::new(Options {
+                quotes: &quotes,
+                length: Some(Length::Short),
+                quote_filter: Some(filter.clone()),
+                served: history.skipped(None),
+                ..Options::default()
+            })
+            .unwrap();
+            history
+                .record(&store, &quotes, &cryptogram, Some(&filter))
+                .unwrap();
+            cryptogram.quote_id.unwrap()
+        };
+
+        let history_quote = pick("History");
+        let picked: Vec<_> = (0..3).map(|_| pick("Poetry")).collect();
+
+        // both poems are served before either is repeated, then the history starts over for poems
+        // only
+        assert_ne!(picked[0], picked[1]);
+        let mut served = store.served_quotes("session:abc").unwrap();
+        served.sort_unstable();
+        let mut expected = vec![picked[2], history_quote];
+        expected.sort_unstable();
+        assert_eq!(served, expected);
+        assert_eq!(History::load(&store, &key).unwrap().served.len(), 2);
+    }
+
+    #[test]
+    fn test_history_seeded() {
+        use crate::cryptogram::Options;
+        use crate::store::MemoryStore;
+
+        let store = MemoryStore::default();
+        let quotes = store
+            .insert_quotes(&[
+                QuoteInput {
+                    text: "a".repeat(60),
+                    ..QuoteInput::default()
+                },
+                QuoteInput {
+                    text: "b".repeat(60),
+                    ..QuoteInput::default()
+                },
+            ])
+            .unwrap();
+        store.record_quote("session:abc", quotes[0].id).unwrap();
+        let history = History::load(
+            &store,
+            &QuoteHistory {
+                scope: HistoryScope::Session,
+                id: "abc".into(),
+            },
+        )
+        .unwrap();
+
+        let quote_id = |seed, served| {
+            Cryptogram::new(Options {
+                quotes: &quotes,
+                length: Some(Length::Short),
+                seed,
+                served,
+                ..Options::default()
+            })
+            .unwrap()
+            .quote_id
+        };
+
+        // without a seed the served quote is skipped, but a seed gives the same quote either way
+        for seed in 0..10 {
+            assert_eq!(quote_id(None, history.skipped(None)), Some(quotes[1].id));
+            assert_eq!(
+                quote_id(Some(seed), history.skipped(Some(seed))),
+                quote_id(Some(seed), None)
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_cache() {
+        use crate::store::MemoryStore;
+
+        let store = MemoryStore::default();
+        let quote = |text: &str| QuoteInput {
+            text: text.into(),
+            ..QuoteInput::default()
+        };
+        store.insert_quotes(&[quote("Veni, vidi, vici.")]).unwrap();
+
+        let cache = QuoteCache::default();
+        assert_eq!(cache.get(&store).unwrap().len(), 1);
+
+        // changes are only seen once the cache is invalidated
+        store.insert_quotes(&[quote("Alea iacta est.")]).unwrap();
+        assert_eq!(cache.get(&store).unwrap().len(), 1);
+        cache.invalidate();
+        assert_eq!(cache.get(&store).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_file_format() {
+        let quotes: Vec<QuoteInput> = serde_json::from_str(
+            r#"[{"quote": "Veni, vidi, vici.", "author": "Caesar", "genre": "History"}]"#,
+        )
+        .unwrap();
+
+        let quote = Quote::new(1, quotes[0].clone());
+        assert_eq!(quote.text, "Veni, vidi, vici.");
+        assert_eq!(quote.genre.as_deref(), Some("History"));
+        assert!(quote.tags.is_empty());
+        assert!(quote.enabled);
+    }
 }
@@ -0,0 +1,338 @@
+//! Solver for monoalphabetic substitution ciphers.
+//!
+//! The solver breaks any ciphertext made by a monoalphabetic substitution, such as an aristocrat
+//! or a patristocrat, without knowing the key. Only the letters are used, so spaces and grouping
+//! do not matter.
+//!
+//! Candidate keys are scored by how much their decryption looks like English, using the log
+//! probabilities of quadgrams (sequences of 4 letters) in `solver/english_quadgrams.txt`. The
+//! table was counted from English prose, one quadgram and its count per line.
+//!
+//! The search is simulated annealing. Each run starts from a random key and repeatedly swaps two
+//! letters of the key. Swaps that improve the score are always kept, and swaps that make it worse
+//! are sometimes kept, less often as the run "cools". This lets the search climb out of keys that
+//! no single swap can improve. The best key over all [`RESTARTS`] runs is returned.
+
+use crate::ciphers::Alphabet;
+use lazy_static::lazy_static;
+use rand::prelude::*;
+
+/// Number of independent annealing runs. The best key over all runs is returned.
+const RESTARTS: usize = 4;
+
+/// Number of keys tried in each annealing run.
+const STEPS: usize = 20_000;
+
+/// Starting temperature of each annealing run, in units of the quadgram score.
+const TEMPERATURE: f64 = 10.0;
+
+/// The most keys [`solve`] can try.
+pub(crate) const MAX_ITERATIONS: usize = RESTARTS * STEPS;
+
+/// The longest ciphertext the API solves, in characters. Every key tried scores the whole text,
+/// so longer texts would tie up a worker.
+pub(crate) const MAX_CIPHERTEXT_LENGTH: usize = 1000;
+
+lazy_static! {
+    static ref QUADGRAMS: Quadgrams = Quadgrams::new(include_str!("solver/english_quadgrams.txt"));
+}
+
+/// Log probabilities of every quadgram.
+struct Quadgrams {
+    /// The log probability of each quadgram, indexed by the letters as base 26 digits.
+    scores: Vec<f64>,
+    /// The expected score of a quadgram of English text.
+    english: f64,
+    /// The expected score of a quadgram of random letters.
+    random: f64,
+}
+
+impl Quadgrams {
+    fn new(contents: &str) -> Self {
+        let alphabet = Alphabet::english();
+        let n = alphabet.len();
+
+        let mut counts = vec![0u64; n.pow(4)];
+        for line in contents.lines() {
+            let (quadgram, count) = line
+                .split_once(' ')
+                .expect("quadgram lines must be \"QUADGRAM COUNT\"");
+
+            let letters: Vec<_> = quadgram
+                .chars()
+                .filter_map(|c| alphabet.index_of(c))
+                .collect();
+
+            counts[index(&letters, n)] = count.parse().expect("quadgram counts must be integers");
+        }
+
+        let total = counts.iter().sum::<u64>() as f64;
+
+        // unseen quadgrams still need a finite score
+        let floor = (0.01 / total).log10();
+
+        let scores: Vec<_> = counts
+            .iter()
+            .map(|&c| {
+                if c == 0 {
+                    floor
+                } else {
+                    (c as f64 / total).log10()
+                }
+            })
+            .collect();
+
+        let english = counts
+            .iter()
+            .zip(&scores)
+            .map(|(&c, &s)| c as f64 / total * s)
+            .sum();
+
+        let random = scores.iter().sum::<f64>() / scores.len() as f64;
+
+        Self {
+            scores,
+            english,
+            random,
+        }
+    }
+
+    /// Sum the scores of every quadgram in `text`.
+    fn score(&self, text: &[usize], n: usize) -> f64 {
+        text.windows(4).map(|w| self.scores[index(w, n)]).sum()
+    }
+}
+
+/// Convert 4 letter indexes into an index into [`Quadgrams::scores`].
+fn index(letters: &[usize], n: usize) -> usize {
+    letters.iter().fold(0, |acc, &l| acc * n + l)
+}
+
+/// The result of solving a ciphertext.
+#[derive(GraphQLObject, Debug)]
+pub struct Solution {
+    /// The decrypted ciphertext.
+    pub plaintext: String,
+    /// The plaintext letter of each ciphertext letter, from A to Z.
+    ///
+    /// Letters that do not appear in the ciphertext are "?".
+    pub mapping: String,
+    /// How much the plaintext looks like English, from 0 to 1.
+    ///
+    /// Random letters score about 0, and English text of the same length scores about 1.
+    pub confidence: f64,
+    /// The number of keys tried before the best key was found.
+    pub iterations: i32,
+}
+
+/// Decrypt `text` with `key`, where `key[c]` is the plaintext letter of ciphertext letter `c`.
+fn decrypt_into(text: &[usize], key: &[usize], out: &mut [usize]) {
+    for (o, &c) in out.iter_mut().zip(text) {
+        *o = key[c];
+    }
+}
+
+/// Anneal from `key` for [`STEPS`] steps.
+///
+/// `key` is left as the best key found, and its score is returned with the value of `iterations`
+/// when it was found. `present_letters` are the ciphertext letters that appear in `text`,
+/// `decrypted` is scratch space the length of `text`, and `iterations` counts every key tried.
+fn anneal<R>(
+    text: &[usize],
+    present_letters: &[usize],
+    key: &mut Vec<usize>,
+    decrypted: &mut [usize],
+    iterations: &mut i32,
+    rng: &mut R,
+) -> (f64, i32)
+where
+    R: Rng + ?Sized,
+{
+    let n = key.len();
+
+    decrypt_into(text, key, decrypted);
+    let mut score = QUADGRAMS.score(decrypted, n);
+
+    let mut best_key = key.clone();
+    let mut best_score = score;
+    let mut best_iterations = *iterations;
+
+    for step in 0..STEPS {
+        let temperature = TEMPERATURE * (1.0 - step as f64 / STEPS as f64);
+
+        // swapping two letters that are not in the ciphertext does nothing
+        let a = *present_letters.choose(rng).unwrap();
+        let b = rng.gen_range(0..n);
+        if a == b {
+            continue;
+        }
+
+        key.swap(a, b);
+        *iterations += 1;
+
+        decrypt_into(text, key, decrypted);
+        let new_score = QUADGRAMS.score(decrypted, n);
+
+        let delta = new_score - score;
+        if delta > 0.0 || rng.gen::<f64>() < (delta / temperature).exp() {
+            score = new_score;
+            if score > best_score {
+                best_score = score;
+                best_key.copy_from_slice(key);
+                best_iterations = *iterations;
+            }
+        } else {
+            key.swap(a, b);
+        }
+    }
+
+    *key = best_key;
+    (best_score, best_iterations)
+}
+
+/// Break a monoalphabetic substitution cipher.
+///
+/// Returns the best key found, which may be wrong for short ciphertexts.
+pub(crate) fn solve<R>(ciphertext: &str, rng: &mut R) -> Solution
+where
+    R: Rng + ?Sized,
+{
+    let alphabet = Alphabet::english();
+    let n = alphabet.len();
+
+    let text: Vec<_> = ciphertext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+
+    let mut present = vec![false; n];
+    for &c in &text {
+        present[c] = true;
+    }
+    let present_letters: Vec<_> = (0..n).filter(|&c| present[c]).collect();
+
+    let mut decrypted = vec![0; text.len()];
+    let mut iterations = 0;
+
+    // there is nothing to score without at least one quadgram
+    let restarts = if text.len() < 4 { 0 } else { RESTARTS };
+
+    let mut best_key: Vec<_> = (0..n).collect();
+    let mut best_score = f64::NEG_INFINITY;
+    let mut best_iterations = 0;
+
+    for _ in 0..restarts {
+        let mut key: Vec<_> = (0..n).collect();
+        key.shuffle(rng);
+
+        let (score, found_at) = anneal(
+            &text,
+            &present_letters,
+            &mut key,
+            &mut decrypted,
+            &mut iterations,
+            rng,
+        );
+        log::debug!("Annealing finished with score {score}");
+
+        if score > best_score {
+            best_score = score;
+            best_key = key;
+            best_iterations = found_at;
+        }
+    }
+
+    let confidence = if restarts == 0 {
+        0.0
+    } else {
+        let per_quadgram = best_score / (text.len() - 3) as f64;
+        ((per_quadgram - QUADGRAMS.random) / (QUADGRAMS.english - QUADGRAMS.random)).clamp(0.0, 1.0)
+    };
+
+    let plaintext = ciphertext
+        .chars()
+        .map(|c| match alphabet.index_of(c) {
+            Some(i) => Alphabet::match_case(alphabet.symbol(best_key[i]), c),
+            None => c,
+        })
+        .collect();
+
+    let mapping = (0..n)
+        .map(|c| {
+            if present[c] {
+                alphabet.symbol(best_key[c]).to_ascii_uppercase()
+            } else {
+                '?'
+            }
+        })
+        .collect();
+
+    Solution {
+        plaintext,
+        mapping,
+        confidence,
+        iterations: best_iterations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PLAINTEXT: &str = "It is a truth universally acknowledged, that a single man in \
+        possession of a good fortune, must be in want of a wife. However little known the \
+        feelings or views of such a man may be on his first entering a neighbourhood, this truth \
+        is so well settled in the minds of the surrounding families.";
+
+    /// Encrypt with the reversed alphabet, so "a" becomes "z".
+    fn encrypt(s: &str) -> String {
+        s.chars()
+            .map(|c| match c {
+                'a'..='z' => (b'z' - (c as u8 - b'a')) as char,
+                'A'..='Z' => (b'Z' - (c as u8 - b'A')) as char,
+                _ => c,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_solve_aristocrat() {
+        let solution = solve(&encrypt(PLAINTEXT), &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(solution.plaintext, PLAINTEXT);
+        assert!(solution.confidence > 0.8);
+        assert!(solution.mapping.ends_with("CBA"));
+    }
+
+    #[test]
+    fn test_solve_patristocrat() {
+        let ciphertext: String = encrypt(PLAINTEXT)
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .collect();
+        let solution = solve(&ciphertext, &mut StdRng::seed_from_u64(0));
+
+        let expected: String = PLAINTEXT
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .collect();
+        assert_eq!(solution.plaintext, expected);
+    }
+
+    #[test]
+    fn test_solve_random_letters() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let ciphertext: String = (0..100).map(|_| rng.gen_range('a'..='z')).collect();
+
+        let solution = solve(&ciphertext, &mut StdRng::seed_from_u64(0));
+        assert!(solution.confidence < 0.5);
+    }
+
+    #[test]
+    fn test_solve_too_short() {
+        let solution = solve("abc", &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(solution.confidence, 0.0);
+        assert_eq!(solution.mapping.matches('?').count(), 23);
+    }
+}
@@ -0,0 +1,493 @@
+//! This module stores cryptograms, tests and hints so they can be looked up by token later, and
+//! the corpus of quotes that cryptograms are made from.
+//!
+//! Every backend implements [`CryptogramStore`], and [`open`] picks one from the configuration:
+//!
+//! - [`PostgresStore`] for a shared server,
+//! - [`SqliteStore`] for a single file, with no database server,
+//! - [`MemoryStore`] for tests and demos, which forgets everything when the server stops.
+
+mod memory;
+mod migrations;
+mod postgres;
+mod sqlite;
+
+pub use self::memory::MemoryStore;
+pub use self::migrations::{Migration, MigrationStatus};
+pub use self::postgres::PostgresStore;
+pub use self::sqlite::SqliteStore;
+use super::ciphers::{CipherError, ErrorKind, Key};
+use super::config::{Config, Storage};
+use super::cryptogram::{Cryptogram, Type};
+use super::hints::{Hint, HintKind};
+use super::quotes::{Quote, QuoteInput};
+use super::test::Question;
+use super::token::Token;
+use std::{error::Error, fmt};
+
+/// An error from a storage backend.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The backend failed, with its message.
+    Backend(String),
+    /// Another quote already has the text of the quote being stored.
+    DuplicateQuote,
+}
+
+impl StoreError {
+    pub fn new(error: String) -> Self {
+        Self::Backend(error)
+    }
+
+    /// A cryptogram with `token` is already stored.
+    pub(crate) fn token_taken(token: &Token) -> Self {
+        Self::new(format!(
+            "A cryptogram with token {} is already stored",
+            token.as_str()
+        ))
+    }
+}
+
+impl Error for StoreError {}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Backend(e) => write!(f, "StorageError: {e}"),
+            StoreError::DuplicateQuote => write!(f, "Another quote already has this text"),
+        }
+    }
+}
+
+impl From<::postgres::Error> for StoreError {
+    fn from(e: ::postgres::Error) -> Self {
+        Self::new(e.to_string())
+    }
+}
+
+impl From<r2d2::Error> for StoreError {
+    fn from(e: r2d2::Error) -> Self {
+        Self::new(format!("Could not get a database connection: {e}"))
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::new(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::new(e.to_string())
+    }
+}
+
+/// Backend errors are reported to API clients as `StorageError`s with a generic message, since the
+/// database's message can give away its schema. The full error is logged.
+///
+/// A duplicate quote is the client's mistake, so it is an `InvalidArgument` blaming `quote.text`.
+impl From<StoreError> for CipherError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::Backend(_) => {
+                log::error!("{e}");
+                CipherError::new(ErrorKind::StorageError, "Internal storage error".into())
+            }
+            StoreError::DuplicateQuote => {
+                CipherError::new(ErrorKind::InvalidArgument, e.to_string()).with_field("quote.text")
+            }
+        }
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// A cryptogram as it was stored.
+#[derive(Debug, Clone)]
+pub struct StoredCryptogram {
+    pub token: Token,
+    pub plaintext: String,
+    /// The keyword, or the whole key for ciphers keyed by a quote.
+    pub keyword: Option<String>,
+    pub r#type: Type,
+    /// The ciphertext. Cryptograms stored before ciphertexts were recorded do not have one.
+    pub ciphertext: Option<String>,
+    /// The full key. Cryptograms stored before full keys were recorded do not have one.
+    pub key: Option<Key>,
+    /// The author of the quote, if it is known.
+    pub author: Option<String>,
+}
+
+impl StoredCryptogram {
+    pub(crate) fn new(cryptogram: &Cryptogram) -> Self {
+        Self {
+            token: cryptogram.token.clone(),
+            plaintext: cryptogram.plaintext.clone(),
+            keyword: cryptogram.key.keyword.clone(),
+            r#type: cryptogram.r#type,
+            ciphertext: Some(cryptogram.ciphertext.clone()),
+            key: Some(cryptogram.key.clone()),
+            author: cryptogram.author.clone(),
+        }
+    }
+
+    /// Build a cryptogram from the columns that SQL backends store.
+    ///
+    /// Cryptograms stored before types were recorded are treated as [`Type::Identity`], so their
+    /// letters are compared as they are.
+    fn from_columns(
+        token: String,
+        plaintext: String,
+        keyword: Option<String>,
+        r#type: Option<String>,
+        ciphertext: Option<String>,
+        key: Option<String>,
+        author: Option<String>,
+    ) -> Self {
+        Self {
+            token: Token::from_stored(token),
+            plaintext,
+            keyword,
+            r#type: r#type
+                .and_then(|t| t.parse().ok())
+                .unwrap_or(Type::Identity),
+            ciphertext,
+            key: key.and_then(|k| serde_json::from_str(&k).ok()),
+            author,
+        }
+    }
+
+    /// The question this cryptogram is in a test.
+    pub(crate) fn question(&self, number: i32, points: i32, hint: Option<String>) -> Question {
+        Question {
+            number,
+            token: self.token.clone(),
+            r#type: self.r#type,
+            ciphertext: self.ciphertext.clone().unwrap_or_default(),
+            points,
+            hint,
+        }
+    }
+}
+
+/// A question of a stored test, with its cryptogram.
+#[derive(Debug)]
+pub struct StoredQuestion {
+    pub question: Question,
+    pub cryptogram: StoredCryptogram,
+}
+
+/// Storage for cryptograms, tests, hints and quotes.
+///
+/// Methods take `&self` so that one store can be shared by every request, which run in parallel.
+/// Backends synchronize internally.
+pub trait CryptogramStore: Send + Sync {
+    /// Store `cryptogram`.
+    ///
+    /// Tokens are random, so two cryptograms should never share one. If the token is already
+    /// stored anyway, the stored cryptogram is kept and this is an error, so a token is never
+    /// handed out for a cryptogram that was not stored.
+    fn insert(&self, cryptogram: &Cryptogram) -> StoreResult<()>;
+
+    /// Look up a cryptogram by token. Tokens issued before tokens were random are stored as their
+    /// decimal digits, so they are found too.
+    fn get(&self, token: &Token) -> StoreResult<Option<StoredCryptogram>>;
+
+    /// Store a test of `questions` and their cryptograms, all or nothing, and return its ID. As in
+    /// [`CryptogramStore::insert`], a token that is already stored is an error.
+    fn insert_test(&self, seed: i32, questions: &[(&Cryptogram, &Question)]) -> StoreResult<i32>;
+
+    /// Look up the questions of a test by ID, in order. Unknown tests have no questions.
+    fn test(&self, id: i32) -> StoreResult<Vec<StoredQuestion>>;
+
+    /// The points of the test question for the cryptogram with `token`, or `None` if the
+    /// cryptogram is not part of a test.
+    fn question_points(&self, token: &Token) -> StoreResult<Option<i32>>;
+
+    /// Record that `hint` was given for the cryptogram with `token`.
+    fn insert_hint(&self, token: &Token, hint: &Hint) -> StoreResult<()>;
+
+    /// The positions of the hints of `kind` already given for `token`.
+    fn hint_positions(&self, token: &Token, kind: HintKind) -> StoreResult<Vec<i32>>;
+
+    /// The total penalty of every hint given for `token`.
+    fn hint_penalty(&self, token: &Token) -> StoreResult<i32>;
+
+    /// Every quote in order of ID, leaving out disabled quotes unless `include_disabled`.
+    fn quotes(&self, include_disabled: bool) -> StoreResult<Vec<Quote>>;
+
+    /// Store `quotes`, all or nothing, and return the stored ones with their IDs.
+    ///
+    /// Quotes with the same text as a stored quote are skipped, so importing the same quotes twice
+    /// adds nothing.
+    fn insert_quotes(&self, quotes: &[QuoteInput]) -> StoreResult<Vec<Quote>>;
+
+    /// Replace the fields of quote `id` with `quote`, and return it. Unknown quotes are `None`.
+    fn update_quote(&self, id: i32, quote: &QuoteInput) -> StoreResult<Option<Quote>>;
+
+    /// Enable or disable quote `id`, and return it. Unknown quotes are `None`.
+    fn set_quote_enabled(&self, id: i32, enabled: bool) -> StoreResult<Option<Quote>>;
+
+    /// The IDs of the quotes served to `history`. See [`crate::quotes::QuoteHistory`].
+    fn served_quotes(&self, history: &str) -> StoreResult<Vec<i32>>;
+
+    /// Record that quote `id` was served to `history`. Recording a quote twice is not an error.
+    fn record_quote(&self, history: &str, id: i32) -> StoreResult<()>;
+
+    /// Forget that the quotes `ids` were served to `history`.
+    fn forget_quotes(&self, history: &str, ids: &[i32]) -> StoreResult<()>;
+
+    /// Forget every quote served to `history`, and return how many there were.
+    fn clear_history(&self, history: &str) -> StoreResult<i32>;
+
+    /// Apply the schema migrations this store has not applied yet, and return them.
+    ///
+    /// Stores with no schema have nothing to apply.
+    fn migrate(&self) -> StoreResult<Vec<&'static Migration>> {
+        Ok(Vec::new())
+    }
+
+    /// Every schema migration of this store, and whether it has been applied.
+    fn migrations(&self) -> StoreResult<Vec<MigrationStatus>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Open the storage backend chosen by `config`.
+///
+/// The schema is not migrated, so call [`CryptogramStore::migrate`] before using the store.
+pub fn open(config: &Config) -> Result<Box<dyn CryptogramStore>, Box<dyn Error>> {
+    Ok(match config.storage {
+        Storage::Postgres => {
+            let pool = r2d2::Pool::builder()
+                .max_size(config.pool_size)
+                .build(config.postgres_manager()?)
+                .map_err(|e| format!("Could not connect to {}: {e}", config.database_url))?;
+            Box::new(PostgresStore::new(pool))
+        }
+        Storage::Sqlite => Box::new(SqliteStore::open(&config.sqlite_file)?),
+        Storage::Memory => Box::<MemoryStore>::default(),
+    })
+}
+
+/// Checks that every backend behaves the same. Each backend's tests run these on a new store.
+#[cfg(test)]
+pub(crate) mod checks {
+    use super::*;
+    use crate::cryptogram::{self, Cryptogram};
+    use crate::test::QuestionSpec;
+
+    fn cryptogram(plaintext: &str) -> Cryptogram {
+        Cryptogram::new(cryptogram::Options {
+            plaintext: Some(plaintext.into()),
+            r#type: Some(Type::Caesar),
+            seed: Some(1),
+            ..cryptogram::Options::default()
+        })
+        .unwrap()
+    }
+
+    pub(crate) fn check_cryptograms(store: &dyn CryptogramStore) {
+        let c = cryptogram("Attack at dawn");
+        store.insert(&c).unwrap();
+
+        // a token can only be stored once, and the first cryptogram is kept
+        let mut other = cryptogram("Retreat at dusk");
+        other.token = c.token.clone();
+        assert!(store.insert(&other).is_err());
+
+        let stored = store.get(&c.token).unwrap().unwrap();
+        assert_eq!(stored.plaintext, c.plaintext);
+        assert_eq!(stored.ciphertext.as_deref(), Some(c.ciphertext.as_str()));
+        assert_eq!(stored.key.as_ref(), Some(&c.key));
+        assert!(matches!(stored.r#type, Type::Caesar));
+        assert_eq!(stored.author, c.author);
+
+        assert!(store.get(&Token::generate()).unwrap().is_none());
+    }
+
+    pub(crate) fn check_tests(store: &dyn CryptogramStore) {
+        let first = cryptogram("Attack at dawn");
+        let second = cryptogram("Retreat at dusk");
+        let spec = QuestionSpec {
+            points: Some(400),
+            ..QuestionSpec::default()
+        };
+        let questions = [
+            Question::new(1, &first, &Default::default()),
+            Question::new(2, &second, &spec),
+        ];
+
+        let id = store
+            .insert_test(7, &[(&first, &questions[0]), (&second, &questions[1])])
+            .unwrap();
+
+        let stored = store.test(id).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].question.number, 2);
+        assert_eq!(stored[1].question.token, second.token);
+        assert_eq!(stored[1].cryptogram.plaintext, second.plaintext);
+
+        assert_eq!(store.question_points(&second.token).unwrap(), Some(400));
+        let other = cryptogram("Hold the line");
+        store.insert(&other).unwrap();
+        assert_eq!(store.question_points(&other.token).unwrap(), None);
+
+        assert!(store.test(id + 1).unwrap().is_empty());
+
+        // a test with a token that is already stored is not stored at all
+        let third = cryptogram("Charge at noon");
+        let question = Question::new(1, &third, &Default::default());
+        assert!(store
+            .insert_test(8, &[(&third, &question), (&first, &questions[0])])
+            .is_err());
+        assert!(store.get(&third.token).unwrap().is_none());
+        assert!(store.test(id + 1).unwrap().is_empty());
+    }
+
+    pub(crate) fn check_hints(store: &dyn CryptogramStore) {
+        let c = cryptogram("Attack at dawn");
+        store.insert(&c).unwrap();
+
+        let hint = |kind: HintKind, position| Hint {
+            kind,
+            position,
+            plaintext: "A".into(),
+            ciphertext: None,
+            penalty: kind.penalty(),
+        };
+        store
+            .insert_hint(&c.token, &hint(HintKind::Letter, 0))
+            .unwrap();
+        store
+            .insert_hint(&c.token, &hint(HintKind::Letter, 3))
+            .unwrap();
+        store
+            .insert_hint(&c.token, &hint(HintKind::Word, 1))
+            .unwrap();
+
+        assert_eq!(
+            store.hint_positions(&c.token, HintKind::Letter).unwrap(),
+            vec![0, 3]
+        );
+        assert_eq!(store.hint_penalty(&c.token).unwrap(), 200);
+        assert_eq!(store.hint_penalty(&Token::generate()).unwrap(), 0);
+    }
+
+    pub(crate) fn check_quotes(store: &dyn CryptogramStore) {
+        let quote = |text: &str| QuoteInput {
+            text: text.into(),
+            author: Some("Anonymous".into()),
+            tags: Some(vec!["proverb".into()]),
+            ..QuoteInput::default()
+        };
+
+        let added = store
+            .insert_quotes(&[quote("Haste makes waste"), quote("Haste makes waste")])
+            .unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].author.as_deref(), Some("Anonymous"));
+        assert_eq!(added[0].tags, ["proverb"]);
+        assert!(added[0].enabled);
+
+        // quotes already stored are skipped
+        let added = store
+            .insert_quotes(&[quote("Haste makes waste"), quote("Practice makes perfect")])
+            .unwrap();
+        assert_eq!(added.len(), 1);
+        let id = added[0].id;
+
+        let updated = store
+            .update_quote(
+                id,
+                &QuoteInput {
+                    genre: Some("Proverb".into()),
+                    ..quote("Practice makes permanent")
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.id, id);
+        assert_eq!(updated.text, "Practice makes permanent");
+        assert_eq!(updated.genre.as_deref(), Some("Proverb"));
+        assert!(store
+            .update_quote(id + 1, &quote("Nope"))
+            .unwrap()
+            .is_none());
+
+        // a quote can keep its text, but not take another quote's
+        assert!(store
+            .update_quote(id, &quote("Practice makes permanent"))
+            .unwrap()
+            .is_some());
+        let err = store
+            .update_quote(id, &quote("Haste makes waste"))
+            .unwrap_err();
+        assert!(CipherError::from(err)
+            .to_string()
+            .starts_with("InvalidArgument"));
+        assert_eq!(
+            store.quotes(false).unwrap()[1].text,
+            "Practice makes permanent"
+        );
+
+        let disabled = store.set_quote_enabled(id, false).unwrap().unwrap();
+        assert!(!disabled.enabled);
+        assert_eq!(store.quotes(false).unwrap().len(), 1);
+        assert_eq!(
+            store.quotes(true).unwrap(),
+            [&store.quotes(false).unwrap()[..], &[disabled]].concat()
+        );
+        assert!(store.set_quote_enabled(id + 1, false).unwrap().is_none());
+    }
+
+    pub(crate) fn check_history(store: &dyn CryptogramStore) {
+        let quotes = store
+            .insert_quotes(&[
+                QuoteInput {
+                    text: "Haste makes waste".into(),
+                    ..QuoteInput::default()
+                },
+                QuoteInput {
+                    text: "Practice makes perfect".into(),
+                    ..QuoteInput::default()
+                },
+            ])
+            .unwrap();
+        let (first, second) = (quotes[0].id, quotes[1].id);
+
+        store.record_quote("session:a", first).unwrap();
+        store.record_quote("session:a", first).unwrap();
+        store.record_quote("session:a", second).unwrap();
+        store.record_quote("session:b", second).unwrap();
+
+        let mut served = store.served_quotes("session:a").unwrap();
+        served.sort_unstable();
+        assert_eq!(served, [first, second]);
+
+        store.forget_quotes("session:a", &[first]).unwrap();
+        assert_eq!(store.served_quotes("session:a").unwrap(), [second]);
+
+        assert_eq!(store.clear_history("session:a").unwrap(), 1);
+        assert!(store.served_quotes("session:a").unwrap().is_empty());
+        assert_eq!(store.served_quotes("session:b").unwrap(), [second]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_message_hidden() {
+        let e = StoreError::new(
+            "duplicate key value violates unique constraint \"quotes_text_key\"".into(),
+        );
+
+        assert_eq!(
+            CipherError::from(e).to_string(),
+            "StorageError: Internal storage error"
+        );
+    }
+}
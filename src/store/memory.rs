@@ -0,0 +1,316 @@
+//! A store that keeps everything in memory.
+
+use super::{CryptogramStore, StoreError, StoreResult, StoredCryptogram, StoredQuestion};
+use crate::cryptogram::Cryptogram;
+use crate::hints::{Hint, HintKind};
+use crate::quotes::{Quote, QuoteInput};
+use crate::test::Question;
+use crate::token::Token;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+/// A question of a test, which refers to its cryptogram by token.
+#[derive(Debug)]
+struct TestQuestion {
+    number: i32,
+    token: Token,
+    points: i32,
+    hint: Option<String>,
+}
+
+#[derive(Debug)]
+struct HintRecord {
+    token: Token,
+    kind: HintKind,
+    position: i32,
+    penalty: i32,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    cryptograms: HashMap<Token, StoredCryptogram>,
+    /// The questions of each test. Test IDs start from 1, like a SQL serial column.
+    tests: Vec<Vec<TestQuestion>>,
+    hints: Vec<HintRecord>,
+    /// Every quote. Quote IDs start from 1, like test IDs.
+    quotes: Vec<Quote>,
+    /// The IDs of the quotes served to each history.
+    history: HashMap<String, HashSet<i32>>,
+}
+
+impl Tables {
+    fn insert(&mut self, cryptogram: &Cryptogram) {
+        self.cryptograms
+            .insert(cryptogram.token.clone(), StoredCryptogram::new(cryptogram));
+    }
+
+    /// An error if a cryptogram with `token` is already stored.
+    fn check_token(&self, token: &Token) -> StoreResult<()> {
+        if self.cryptograms.contains_key(token) {
+            return Err(StoreError::token_taken(token));
+        }
+
+        Ok(())
+    }
+
+    fn quote(&mut self, id: i32) -> Option<&mut Quote> {
+        usize::try_from(id - 1)
+            .ok()
+            .and_then(|i| self.quotes.get_mut(i))
+    }
+}
+
+/// Stores cryptograms in memory, so they are lost when the server stops.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStore {
+    fn tables(&self) -> StoreResult<MutexGuard<'_, Tables>> {
+        self.tables
+            .lock()
+            .map_err(|_| StoreError::new("A request panicked while writing to the store".into()))
+    }
+}
+
+impl CryptogramStore for MemoryStore {
+    fn insert(&self, cryptogram: &Cryptogram) -> StoreResult<()> {
+        let mut tables = self.tables()?;
+        tables.check_token(&cryptogram.token)?;
+        tables.insert(cryptogram);
+
+        Ok(())
+    }
+
+    fn get(&self, token: &Token) -> StoreResult<Option<StoredCryptogram>> {
+        Ok(self.tables()?.cryptograms.get(token).cloned())
+    }
+
+    fn insert_test(&self, _seed: i32, questions: &[(&Cryptogram, &Question)]) -> StoreResult<i32> {
+        let mut tables = self.tables()?;
+        let mut tokens = HashSet::new();
+        for (cryptogram, _) in questions {
+            tables.check_token(&cryptogram.token)?;
+            if !tokens.insert(&cryptogram.token) {
+                return Err(StoreError::token_taken(&cryptogram.token));
+            }
+        }
+
+        let mut test = Vec::with_capacity(questions.len());
+        for (cryptogram, question) in questions {
+            tables.insert(cryptogram);
+            test.push(TestQuestion {
+                number: question.number,
+                token: question.token.clone(),
+                points: question.points,
+                hint: question.hint.clone(),
+            });
+        }
+        tables.tests.push(test);
+
+        Ok(tables.tests.len() as i32)
+    }
+
+    fn test(&self, id: i32) -> StoreResult<Vec<StoredQuestion>> {
+        let tables = self.tables()?;
+
+        let test = match usize::try_from(id - 1)
+            .ok()
+            .and_then(|i| tables.tests.get(i))
+        {
+            Some(test) => test,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(test
+            .iter()
+            .filter_map(|q| {
+                let cryptogram = tables.cryptograms.get(&q.token)?.clone();
+                Some(StoredQuestion {
+                    question: cryptogram.question(q.number, q.points, q.hint.clone()),
+                    cryptogram,
+                })
+            })
+            .collect())
+    }
+
+    fn question_points(&self, token: &Token) -> StoreResult<Option<i32>> {
+        Ok(self
+            .tables()?
+            .tests
+            .iter()
+            .flatten()
+            .find(|q| &q.token == token)
+            .map(|q| q.points))
+    }
+
+    fn insert_hint(&self, token: &Token, hint: &Hint) -> StoreResult<()> {
+        self.tables()?.hints.push(HintRecord {
+            token: token.clone(),
+            kind: hint.kind,
+            position: hint.position,
+            penalty: hint.penalty,
+        });
+        Ok(())
+    }
+
+    fn hint_positions(&self, token: &Token, kind: HintKind) -> StoreResult<Vec<i32>> {
+        Ok(self
+            .tables()?
+            .hints
+            .iter()
+            .filter(|h| &h.token == token && h.kind == kind)
+            .map(|h| h.position)
+            .collect())
+    }
+
+    fn hint_penalty(&self, token: &Token) -> StoreResult<i32> {
+        Ok(self
+            .tables()?
+            .hints
+            .iter()
+            .filter(|h| &h.token == token)
+            .map(|h| h.penalty)
+            .sum())
+    }
+
+    fn quotes(&self, include_disabled: bool) -> StoreResult<Vec<Quote>> {
+        Ok(self
+            .tables()?
+            .quotes
+            .iter()
+            .filter(|q| q.enabled || include_disabled)
+            .cloned()
+            .collect())
+    }
+
+    fn insert_quotes(&self, quotes: &[QuoteInput]) -> StoreResult<Vec<Quote>> {
+        let mut tables = self.tables()?;
+
+        let mut inserted = Vec::new();
+        for quote in quotes {
+            if tables.quotes.iter().any(|q| q.text == quote.text) {
+                continue;
+            }
+
+            let quote = Quote::new(tables.quotes.len() as i32 + 1, quote.clone());
+            tables.quotes.push(quote.clone());
+            inserted.push(quote);
+        }
+
+        Ok(inserted)
+    }
+
+    fn update_quote(&self, id: i32, quote: &QuoteInput) -> StoreResult<Option<Quote>> {
+        let mut tables = self.tables()?;
+
+        if tables
+            .quotes
+            .iter()
+            .any(|q| q.text == quote.text && q.id != id)
+        {
+            return Err(StoreError::DuplicateQuote);
+        }
+
+        Ok(tables.quote(id).map(|stored| {
+            *stored = Quote {
+                enabled: stored.enabled,
+                ..Quote::new(id, quote.clone())
+            };
+            stored.clone()
+        }))
+    }
+
+    fn set_quote_enabled(&self, id: i32, enabled: bool) -> StoreResult<Option<Quote>> {
+        Ok(self.tables()?.quote(id).map(|stored| {
+            stored.enabled = enabled;
+            stored.clone()
+        }))
+    }
+
+    fn served_quotes(&self, history: &str) -> StoreResult<Vec<i32>> {
+        Ok(self
+            .tables()?
+            .history
+            .get(history)
+            .map(|served| served.iter().copied().collect())
+            .unwrap_or_default())
+    }
+
+    fn record_quote(&self, history: &str, id: i32) -> StoreResult<()> {
+        self.tables()?
+            .history
+            .entry(history.into())
+            .or_default()
+            .insert(id);
+        Ok(())
+    }
+
+    fn forget_quotes(&self, history: &str, ids: &[i32]) -> StoreResult<()> {
+        if let Some(served) = self.tables()?.history.get_mut(history) {
+            served.retain(|id| !ids.contains(id));
+        }
+        Ok(())
+    }
+
+    fn clear_history(&self, history: &str) -> StoreResult<i32> {
+        Ok(self
+            .tables()?
+            .history
+            .remove(history)
+            .map_or(0, |served| served.len() as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::checks;
+
+    #[test]
+    fn test_cryptograms() {
+        checks::check_cryptograms(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_tests() {
+        checks::check_tests(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_hints() {
+        checks::check_hints(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_quotes() {
+        checks::check_quotes(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_history() {
+        checks::check_history(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_parallel() {
+        let store = MemoryStore::default();
+        let token = Token::generate();
+        let hint = Hint {
+            kind: HintKind::Letter,
+            position: 0,
+            plaintext: "A".into(),
+            ciphertext: None,
+            penalty: 50,
+        };
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| store.insert_hint(&token, &hint).unwrap());
+            }
+        });
+
+        assert_eq!(store.hint_penalty(&token).unwrap(), 200);
+    }
+}
@@ -0,0 +1,250 @@
+//! Versioned schema migrations for the SQL backends.
+//!
+//! Each backend has its own list of migrations, embedded from `migrations/<backend>/`. Applied
+//! versions are recorded in the `schema_migrations` table, and each migration runs in a
+//! transaction with its record, so a failed migration leaves nothing behind.
+//!
+//! Migrations are never edited once released. To change the schema, add a new file and append it
+//! to the list.
+
+use super::StoreResult;
+
+/// A change to the schema.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Migration {
+    /// The version, starting from 1. Migrations are applied in order of version.
+    pub version: i32,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+/// A migration, and whether it has been applied.
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub migration: &'static Migration,
+    pub applied: bool,
+}
+
+pub(super) static POSTGRES: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_cryptograms",
+        sql: include_str!("../../migrations/postgres/0001_create_cryptograms.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "widen_columns",
+        sql: include_str!("../../migrations/postgres/0002_widen_columns.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_type_and_keys",
+        sql: include_str!("../../migrations/postgres/0003_add_type_and_keys.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "create_hints_and_tests",
+        sql: include_str!("../../migrations/postgres/0004_create_hints_and_tests.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "add_author_and_created_at",
+        sql: include_str!("../../migrations/postgres/0005_add_author_and_created_at.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "random_tokens",
+        sql: include_str!("../../migrations/postgres/0006_random_tokens.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "create_quotes",
+        sql: include_str!("../../migrations/postgres/0007_create_quotes.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "create_quote_history",
+        sql: include_str!("../../migrations/postgres/0008_create_quote_history.sql"),
+    },
+];
+
+pub(super) static SQLITE: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_tables",
+        sql: include_str!("../../migrations/sqlite/0001_create_tables.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "add_author_and_created_at",
+        sql: include_str!("../../migrations/sqlite/0002_add_author_and_created_at.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "random_tokens",
+        sql: include_str!("../../migrations/sqlite/0003_random_tokens.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "create_quotes",
+        sql: include_str!("../../migrations/sqlite/0004_create_quotes.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "create_quote_history",
+        sql: include_str!("../../migrations/sqlite/0005_create_quote_history.sql"),
+    },
+];
+
+/// A database connection that migrations can be applied to.
+pub(super) trait Migrate {
+    /// The versions already applied, creating the `schema_migrations` table if needed.
+    fn applied(&mut self) -> StoreResult<Vec<i32>>;
+
+    /// Run `migration` and record it, all or nothing.
+    fn apply(&mut self, migration: &Migration) -> StoreResult<()>;
+}
+
+impl Migrate for postgres::Client {
+    fn applied(&mut self) -> StoreResult<Vec<i32>> {
+        self.batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INT PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+        )?;
+
+        Ok(self
+            .query("SELECT version FROM schema_migrations", &[])?
+            .iter()
+            .map(|r| r.get(0))
+            .collect())
+    }
+
+    fn apply(&mut self, migration: &Migration) -> StoreResult<()> {
+        let mut transaction = self.transaction()?;
+        transaction.batch_execute(migration.sql)?;
+        transaction.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+            &[&migration.version, &migration.name],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+impl Migrate for rusqlite::Connection {
+    fn applied(&mut self) -> StoreResult<Vec<i32>> {
+        self.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )?;
+
+        let mut statement = self.prepare("SELECT version FROM schema_migrations")?;
+        let versions = statement
+            .query_map([], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(versions)
+    }
+
+    fn apply(&mut self, migration: &Migration) -> StoreResult<()> {
+        let transaction = self.transaction()?;
+        transaction.execute_batch(migration.sql)?;
+        transaction.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)",
+            rusqlite::params![migration.version, migration.name],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+/// Every migration in `migrations`, and whether `db` has applied it.
+pub(super) fn status<M: Migrate + ?Sized>(
+    db: &mut M,
+    migrations: &'static [Migration],
+) -> StoreResult<Vec<MigrationStatus>> {
+    let applied = db.applied()?;
+
+    Ok(migrations
+        .iter()
+        .map(|migration| MigrationStatus {
+            migration,
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+/// Apply the migrations in `migrations` that `db` has not applied, in order, and return them.
+pub(super) fn up<M: Migrate + ?Sized>(
+    db: &mut M,
+    migrations: &'static [Migration],
+) -> StoreResult<Vec<&'static Migration>> {
+    let mut applied = Vec::new();
+    for status in status(db, migrations)? {
+        if !status.applied {
+            log::info!(
+                "Applying migration {:04} {}",
+                status.migration.version,
+                status.migration.name
+            );
+            db.apply(status.migration)?;
+            applied.push(status.migration);
+        }
+    }
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_in_order() {
+        for migrations in [POSTGRES, SQLITE] {
+            for (i, migration) in migrations.iter().enumerate() {
+                assert_eq!(migration.version, i as i32 + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sqlite_up() {
+        let mut db = rusqlite::Connection::open_in_memory().unwrap();
+        assert!(status(&mut db, SQLITE).unwrap().iter().all(|s| !s.applied));
+
+        // a database from before random tokens, with an integer token
+        assert_eq!(up(&mut db, &SQLITE[..2]).unwrap().len(), 2);
+        db.execute_batch(
+            "INSERT INTO cryptograms (token, plaintext) VALUES (1234, 'plaintext');
+            INSERT INTO tests (id, seed) VALUES (1, 0);
+            INSERT INTO test_questions (test_id, number, token) VALUES (1, 1, 1234);",
+        )
+        .unwrap();
+
+        let applied = up(&mut db, SQLITE).unwrap();
+        assert_eq!(applied, SQLITE[2..].iter().collect::<Vec<_>>());
+        assert!(status(&mut db, SQLITE).unwrap().iter().all(|s| s.applied));
+
+        for table in ["cryptograms", "test_questions"] {
+            let token: (String, String) = db
+                .query_row(
+                    &format!("SELECT token, typeof(token) FROM {table}"),
+                    [],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .unwrap();
+            assert_eq!(token, ("1234".to_string(), "text".to_string()));
+        }
+
+        assert!(up(&mut db, SQLITE).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+//! A store backed by Postgres.
+
+use super::migrations::{self, Migration, MigrationStatus};
+use super::{CryptogramStore, StoreError, StoreResult, StoredCryptogram, StoredQuestion};
+use crate::cryptogram::Cryptogram;
+use crate::hints::{Hint, HintKind};
+use crate::quotes::{Quote, QuoteInput};
+use crate::test::Question;
+use crate::token::Token;
+use postgres::error::SqlState;
+use postgres::{GenericClient, Row};
+use postgres_native_tls::MakeTlsConnector;
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+
+type Manager = PostgresConnectionManager<MakeTlsConnector>;
+
+/// The columns of `cryptograms` read by [`from_row`], prefixed by `c.`.
+const COLUMNS: &str =
+    "c.token, c.plaintext, c.key, c.type, c.ciphertext, c.structured_key, c.author";
+
+/// Read a cryptogram from the first 7 columns of `row`, which are [`COLUMNS`].
+fn from_row(row: &Row) -> StoredCryptogram {
+    StoredCryptogram::from_columns(
+        row.get(0),
+        row.get(1),
+        row.get(2),
+        row.get(3),
+        row.get(4),
+        row.get(5),
+        row.get(6),
+    )
+}
+
+/// The columns of `quotes` read by [`quote_from_row`].
+const QUOTE_COLUMNS: &str = "id, text, author, genre, source, language, tags, enabled";
+
+/// Read a quote from the columns of `row`, which are [`QUOTE_COLUMNS`].
+fn quote_from_row(row: &Row) -> Quote {
+    let tags: String = row.get(6);
+    Quote {
+        id: row.get(0),
+        text: row.get(1),
+        author: row.get(2),
+        genre: row.get(3),
+        source: row.get(4),
+        language: row.get(5),
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        enabled: row.get(7),
+    }
+}
+
+fn insert<C: GenericClient>(db: &mut C, cryptogram: &Cryptogram) -> StoreResult<()> {
+    let inserted = db.execute(
+        "INSERT INTO cryptograms
+        (token, plaintext, key, type, ciphertext, structured_key, author)
+        VALUES($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (token) DO NOTHING",
+        &[
+            &cryptogram.token.as_str(),
+            &cryptogram.plaintext,
+            &cryptogram.key.keyword,
+            &cryptogram.r#type.to_string(),
+            &cryptogram.ciphertext,
+            &serde_json::to_string(&cryptogram.key)?,
+            &cryptogram.author,
+        ],
+    )?;
+
+    if inserted == 0 {
+        return Err(StoreError::token_taken(&cryptogram.token));
+    }
+
+    Ok(())
+}
+
+/// Stores cryptograms in Postgres, with a pool of connections so requests run in parallel.
+pub struct PostgresStore {
+    pool: Pool<Manager>,
+}
+
+impl PostgresStore {
+    /// Use the database `pool` connects to. Call [`CryptogramStore::migrate`] to set up the
+    /// tables.
+    pub fn new(pool: Pool<Manager>) -> Self {
+        Self { pool }
+    }
+
+    fn db(&self) -> StoreResult<PooledConnection<Manager>> {
+        Ok(self.pool.get()?)
+    }
+}
+
+impl CryptogramStore for PostgresStore {
+    fn insert(&self, cryptogram: &Cryptogram) -> StoreResult<()> {
+        insert(&mut *self.db()?, cryptogram)
+    }
+
+    fn get(&self, token: &Token) -> StoreResult<Option<StoredCryptogram>> {
+        let row = self.db()?.query_opt(
+            &format!("SELECT {COLUMNS} FROM cryptograms c WHERE c.token = $1"),
+            &[&token.as_str()],
+        )?;
+
+        Ok(row.as_ref().map(from_row))
+    }
+
+    fn insert_test(&self, seed: i32, questions: &[(&Cryptogram, &Question)]) -> StoreResult<i32> {
+        let mut db = self.db()?;
+        let mut transaction = db.transaction()?;
+
+        let id: i32 = transaction
+            .query_one(
+                "INSERT INTO tests (seed) VALUES ($1) RETURNING id",
+                &[&seed],
+            )?
+            .get(0);
+
+        for (cryptogram, question) in questions {
+            insert(&mut transaction, cryptogram)?;
+            transaction.execute(
+                "INSERT INTO test_questions (test_id, number, token, points, hint)
+                VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &id,
+                    &question.number,
+                    &question.token.as_str(),
+                    &question.points,
+                    &question.hint,
+                ],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(id)
+    }
+
+    fn test(&self, id: i32) -> StoreResult<Vec<StoredQuestion>> {
+        let rows = self.db()?.query(
+            &format!(
+                "SELECT {COLUMNS}, q.number, q.points, q.hint
+                FROM test_questions q JOIN cryptograms c ON c.token = q.token
+                WHERE q.test_id = $1
+                ORDER BY q.number"
+            ),
+            &[&id],
+        )?;
+
+        Ok(rows
+            .iter()
+            .map(|r| {
+                let cryptogram = from_row(r);
+                StoredQuestion {
+                    question: cryptogram.question(r.get(7), r.get(8), r.get(9)),
+                    cryptogram,
+                }
+            })
+            .collect())
+    }
+
+    fn question_points(&self, token: &Token) -> StoreResult<Option<i32>> {
+        Ok(self
+            .db()?
+            .query_opt(
+                "SELECT points FROM test_questions WHERE token = $1",
+                &[&token.as_str()],
+            )?
+            .map(|r| r.get(0)))
+    }
+
+    fn insert_hint(&self, token: &Token, hint: &Hint) -> StoreResult<()> {
+        self.db()?.execute(
+            "INSERT INTO hints (token, kind, position, penalty) VALUES($1, $2, $3, $4)",
+            &[
+                &token.as_str(),
+                &hint.kind.to_string(),
+                &hint.position,
+                &hint.penalty,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn hint_positions(&self, token: &Token, kind: HintKind) -> StoreResult<Vec<i32>> {
+        Ok(self
+            .db()?
+            .query(
+                "SELECT position FROM hints WHERE token = $1 AND kind = $2",
+                &[&token.as_str(), &kind.to_string()],
+            )?
+            .iter()
+            .map(|r| r.get(0))
+            .collect())
+    }
+
+    fn hint_penalty(&self, token: &Token) -> StoreResult<i32> {
+        let penalty: i64 = self
+            .db()?
+            .query_one(
+                "SELECT COALESCE(SUM(penalty), 0) FROM hints WHERE token = $1",
+                &[&token.as_str()],
+            )?
+            .get(0);
+
+        Ok(penalty as i32)
+    }
+
+    fn quotes(&self, include_disabled: bool) -> StoreResult<Vec<Quote>> {
+        Ok(self
+            .db()?
+            .query(
+                &format!("SELECT {QUOTE_COLUMNS} FROM quotes WHERE enabled OR $1 ORDER BY id"),
+                &[&include_disabled],
+            )?
+            .iter()
+            .map(quote_from_row)
+            .collect())
+    }
+
+    fn insert_quotes(&self, quotes: &[QuoteInput]) -> StoreResult<Vec<Quote>> {
+        let mut db = self.db()?;
+        let mut transaction = db.transaction()?;
+
+        let mut inserted = Vec::new();
+        for q in quotes {
+            let row = transaction.query_opt(
+                &format!(
+                    "INSERT INTO quotes (text, author, genre, source, language, tags)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    ON CONFLICT (text) DO NOTHING
+                    RETURNING {QUOTE_COLUMNS}"
+                ),
+                &[
+                    &q.text,
+                    &q.author,
+                    &q.genre,
+                    &q.source,
+                    &q.language,
+                    &serde_json::to_string(q.tags())?,
+                ],
+            )?;
+            inserted.extend(row.as_ref().map(quote_from_row));
+        }
+
+        transaction.commit()?;
+
+        Ok(inserted)
+    }
+
+    fn update_quote(&self, id: i32, q: &QuoteInput) -> StoreResult<Option<Quote>> {
+        let row = match self.db()?.query_opt(
+            &format!(
+                "UPDATE quotes
+                SET text = $2, author = $3, genre = $4, source = $5, language = $6, tags = $7
+                WHERE id = $1
+                RETURNING {QUOTE_COLUMNS}"
+            ),
+            &[
+                &id,
+                &q.text,
+                &q.author,
+                &q.genre,
+                &q.source,
+                &q.language,
+                &serde_json::to_string(q.tags())?,
+            ],
+        ) {
+            // text is the only unique column that can change
+            Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+                return Err(StoreError::DuplicateQuote)
+            }
+            row => row?,
+        };
+
+        Ok(row.as_ref().map(quote_from_row))
+    }
+
+    fn set_quote_enabled(&self, id: i32, enabled: bool) -> StoreResult<Option<Quote>> {
+        let row = self.db()?.query_opt(
+            &format!("UPDATE quotes SET enabled = $2 WHERE id = $1 RETURNING {QUOTE_COLUMNS}"),
+            &[&id, &enabled],
+        )?;
+
+        Ok(row.as_ref().map(quote_from_row))
+    }
+
+    fn served_quotes(&self, history: &str) -> StoreResult<Vec<i32>> {
+        Ok(self
+            .db()?
+            .query(
+                "SELECT quote_id FROM quote_history WHERE history = $1",
+                &[&history],
+            )?
+            .iter()
+            .map(|r| r.get(0))
+            .collect())
+    }
+
+    fn record_quote(&self, history: &str, id: i32) -> StoreResult<()> {
+        self.db()?.execute(
+            "INSERT INTO quote_history (history, quote_id) VALUES ($1, $2)
+            ON CONFLICT (history, quote_id) DO UPDATE SET served_at = now()",
+            &[&history, &id],
+        )?;
+
+        Ok(())
+    }
+
+    fn forget_quotes(&self, history: &str, ids: &[i32]) -> StoreResult<()> {
+        self.db()?.execute(
+            "DELETE FROM quote_history WHERE history = $1 AND quote_id = ANY($2)",
+            &[&history, &ids],
+        )?;
+
+        Ok(())
+    }
+
+    fn clear_history(&self, history: &str) -> StoreResult<i32> {
+        let forgotten = self
+            .db()?
+            .execute("DELETE FROM quote_history WHERE history = $1", &[&history])?;
+
+        Ok(forgotten as i32)
+    }
+
+    fn migrate(&self) -> StoreResult<Vec<&'static Migration>> {
+        migrations::up(&mut *self.db()?, migrations::POSTGRES)
+    }
+
+    fn migrations(&self) -> StoreResult<Vec<MigrationStatus>> {
+        migrations::status(&mut *self.db()?, migrations::POSTGRES)
+    }
+}
@@ -0,0 +1,417 @@
+//! A store backed by a SQLite file.
+
+use super::migrations::{self, Migration, MigrationStatus};
+use super::{CryptogramStore, StoreError, StoreResult, StoredCryptogram, StoredQuestion};
+use crate::cryptogram::Cryptogram;
+use crate::hints::{Hint, HintKind};
+use crate::quotes::{Quote, QuoteInput};
+use crate::test::Question;
+use crate::token::Token;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// The columns of `cryptograms` read by [`from_row`], prefixed by `c.`.
+const COLUMNS: &str =
+    "c.token, c.plaintext, c.key, c.type, c.ciphertext, c.structured_key, c.author";
+
+/// Read a cryptogram from the first 7 columns of `row`, which are [`COLUMNS`].
+fn from_row(row: &Row) -> rusqlite::Result<StoredCryptogram> {
+    Ok(StoredCryptogram::from_columns(
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+/// The columns of `quotes` read by [`quote_from_row`].
+const QUOTE_COLUMNS: &str = "id, text, author, genre, source, language, tags, enabled";
+
+/// Read a quote from the columns of `row`, which are [`QUOTE_COLUMNS`].
+fn quote_from_row(row: &Row) -> rusqlite::Result<Quote> {
+    let tags: String = row.get(6)?;
+    Ok(Quote {
+        id: row.get(0)?,
+        text: row.get(1)?,
+        author: row.get(2)?,
+        genre: row.get(3)?,
+        source: row.get(4)?,
+        language: row.get(5)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        enabled: row.get(7)?,
+    })
+}
+
+fn quote(db: &Connection, id: i32) -> StoreResult<Option<Quote>> {
+    Ok(db
+        .query_row(
+            &format!("SELECT {QUOTE_COLUMNS} FROM quotes WHERE id = ?1"),
+            [id],
+            quote_from_row,
+        )
+        .optional()?)
+}
+
+fn insert(db: &Connection, cryptogram: &Cryptogram) -> StoreResult<()> {
+    let inserted = db.execute(
+        "INSERT OR IGNORE INTO cryptograms
+        (token, plaintext, key, type, ciphertext, structured_key, author, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime('now'))",
+        params![
+            cryptogram.token.as_str(),
+            cryptogram.plaintext,
+            cryptogram.key.keyword,
+            cryptogram.r#type.to_string(),
+            cryptogram.ciphertext,
+            serde_json::to_string(&cryptogram.key)?,
+            cryptogram.author,
+        ],
+    )?;
+
+    if inserted == 0 {
+        return Err(StoreError::token_taken(&cryptogram.token));
+    }
+
+    Ok(())
+}
+
+/// Stores cryptograms in a SQLite database, for running without a database server.
+pub struct SqliteStore {
+    db: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open or create the database at `path`.
+    pub fn open(path: &Path) -> StoreResult<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    /// Use the database `db` is connected to. Call [`CryptogramStore::migrate`] to set up the
+    /// tables.
+    pub fn new(db: Connection) -> StoreResult<Self> {
+        db.execute_batch("PRAGMA foreign_keys = ON")?;
+
+        Ok(Self { db: Mutex::new(db) })
+    }
+
+    fn db(&self) -> StoreResult<MutexGuard<'_, Connection>> {
+        self.db
+            .lock()
+            .map_err(|_| StoreError::new("A request panicked while using the database".into()))
+    }
+}
+
+impl CryptogramStore for SqliteStore {
+    fn insert(&self, cryptogram: &Cryptogram) -> StoreResult<()> {
+        insert(&*self.db()?, cryptogram)
+    }
+
+    fn get(&self, token: &Token) -> StoreResult<Option<StoredCryptogram>> {
+        Ok(self
+            .db()?
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM cryptograms c WHERE c.token = ?1"),
+                [token.as_str()],
+                from_row,
+            )
+            .optional()?)
+    }
+
+    fn insert_test(&self, seed: i32, questions: &[(&Cryptogram, &Question)]) -> StoreResult<i32> {
+        let mut db = self.db()?;
+        let transaction = db.transaction()?;
+
+        transaction.execute("INSERT INTO tests (seed) VALUES (?1)", [seed])?;
+        let id = transaction.last_insert_rowid() as i32;
+
+        for (cryptogram, question) in questions {
+            insert(&transaction, cryptogram)?;
+            transaction.execute(
+                "INSERT INTO test_questions (test_id, number, token, points, hint)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id,
+                    question.number,
+                    question.token.as_str(),
+                    question.points,
+                    question.hint,
+                ],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(id)
+    }
+
+    fn test(&self, id: i32) -> StoreResult<Vec<StoredQuestion>> {
+        let db = self.db()?;
+        let mut statement = db.prepare(&format!(
+            "SELECT {COLUMNS}, q.number, q.points, q.hint
+            FROM test_questions q JOIN cryptograms c ON c.token = q.token
+            WHERE q.test_id = ?1
+            ORDER BY q.number"
+        ))?;
+
+        let questions = statement
+            .query_map([id], |r| {
+                let cryptogram = from_row(r)?;
+                Ok(StoredQuestion {
+                    question: cryptogram.question(r.get(7)?, r.get(8)?, r.get(9)?),
+                    cryptogram,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(questions)
+    }
+
+    fn question_points(&self, token: &Token) -> StoreResult<Option<i32>> {
+        Ok(self
+            .db()?
+            .query_row(
+                "SELECT points FROM test_questions WHERE token = ?1",
+                [token.as_str()],
+                |r| r.get(0),
+            )
+            .optional()?)
+    }
+
+    fn insert_hint(&self, token: &Token, hint: &Hint) -> StoreResult<()> {
+        self.db()?.execute(
+            "INSERT INTO hints (token, kind, position, penalty) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                token.as_str(),
+                hint.kind.to_string(),
+                hint.position,
+                hint.penalty
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn hint_positions(&self, token: &Token, kind: HintKind) -> StoreResult<Vec<i32>> {
+        let db = self.db()?;
+        let mut statement =
+            db.prepare("SELECT position FROM hints WHERE token = ?1 AND kind = ?2")?;
+
+        let positions = statement
+            .query_map(params![token.as_str(), kind.to_string()], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(positions)
+    }
+
+    fn hint_penalty(&self, token: &Token) -> StoreResult<i32> {
+        Ok(self.db()?.query_row(
+            "SELECT COALESCE(SUM(penalty), 0) FROM hints WHERE token = ?1",
+            [token.as_str()],
+            |r| r.get(0),
+        )?)
+    }
+
+    fn quotes(&self, include_disabled: bool) -> StoreResult<Vec<Quote>> {
+        let db = self.db()?;
+        let mut statement = db.prepare(&format!(
+            "SELECT {QUOTE_COLUMNS} FROM quotes WHERE enabled OR ?1 ORDER BY id"
+        ))?;
+
+        let quotes = statement
+            .query_map([include_disabled], quote_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(quotes)
+    }
+
+    fn insert_quotes(&self, quotes: &[QuoteInput]) -> StoreResult<Vec<Quote>> {
+        let mut db = self.db()?;
+        let transaction = db.transaction()?;
+
+        let mut inserted = Vec::new();
+        for q in quotes {
+            let changed = transaction.execute(
+                "INSERT OR IGNORE INTO quotes (text, author, genre, source, language, tags)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    q.text,
+                    q.author,
+                    q.genre,
+                    q.source,
+                    q.language,
+                    serde_json::to_string(q.tags())?,
+                ],
+            )?;
+            if changed > 0 {
+                let id = transaction.last_insert_rowid() as i32;
+                inserted.extend(quote(&transaction, id)?);
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(inserted)
+    }
+
+    fn update_quote(&self, id: i32, q: &QuoteInput) -> StoreResult<Option<Quote>> {
+        let db = self.db()?;
+        let updated = db.execute(
+            "UPDATE quotes
+            SET text = ?2, author = ?3, genre = ?4, source = ?5, language = ?6, tags = ?7
+            WHERE id = ?1",
+            params![
+                id,
+                q.text,
+                q.author,
+                q.genre,
+                q.source,
+                q.language,
+                serde_json::to_string(q.tags())?,
+            ],
+        );
+        match updated {
+            // text is the only unique column that can change
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE =>
+            {
+                return Err(StoreError::DuplicateQuote)
+            }
+            updated => updated?,
+        };
+
+        quote(&db, id)
+    }
+
+    fn set_quote_enabled(&self, id: i32, enabled: bool) -> StoreResult<Option<Quote>> {
+        let db = self.db()?;
+        db.execute(
+            "UPDATE quotes SET enabled = ?2 WHERE id = ?1",
+            params![id, enabled],
+        )?;
+
+        quote(&db, id)
+    }
+
+    fn served_quotes(&self, history: &str) -> StoreResult<Vec<i32>> {
+        let db = self.db()?;
+        let mut statement = db.prepare("SELECT quote_id FROM quote_history WHERE history = ?1")?;
+
+        let served = statement
+            .query_map([history], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(served)
+    }
+
+    fn record_quote(&self, history: &str, id: i32) -> StoreResult<()> {
+        self.db()?.execute(
+            "INSERT OR REPLACE INTO quote_history (history, quote_id, served_at)
+            VALUES (?1, ?2, datetime('now'))",
+            params![history, id],
+        )?;
+
+        Ok(())
+    }
+
+    fn forget_quotes(&self, history: &str, ids: &[i32]) -> StoreResult<()> {
+        let mut db = self.db()?;
+        let transaction = db.transaction()?;
+
+        for id in ids {
+            transaction.execute(
+                "DELETE FROM quote_history WHERE history = ?1 AND quote_id = ?2",
+                params![history, id],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn clear_history(&self, history: &str) -> StoreResult<i32> {
+        let forgotten = self
+            .db()?
+            .execute("DELETE FROM quote_history WHERE history = ?1", [history])?;
+
+        Ok(forgotten as i32)
+    }
+
+    fn migrate(&self) -> StoreResult<Vec<&'static Migration>> {
+        migrations::up(&mut *self.db()?, migrations::SQLITE)
+    }
+
+    fn migrations(&self) -> StoreResult<Vec<MigrationStatus>> {
+        migrations::status(&mut *self.db()?, migrations::SQLITE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::checks;
+
+    fn store() -> SqliteStore {
+        let store = SqliteStore::new(Connection::open_in_memory().unwrap()).unwrap();
+        store.migrate().unwrap();
+        store
+    }
+
+    #[test]
+    fn test_cryptograms() {
+        checks::check_cryptograms(&store());
+    }
+
+    #[test]
+    fn test_tests() {
+        checks::check_tests(&store());
+    }
+
+    #[test]
+    fn test_hints() {
+        checks::check_hints(&store());
+    }
+
+    #[test]
+    fn test_quotes() {
+        checks::check_quotes(&store());
+    }
+
+    #[test]
+    fn test_history() {
+        checks::check_history(&store());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let store = SqliteStore::new(Connection::open_in_memory().unwrap()).unwrap();
+        assert!(store.migrations().unwrap().iter().all(|m| !m.applied));
+
+        assert_eq!(store.migrate().unwrap().len(), migrations::SQLITE.len());
+        assert!(store.migrations().unwrap().iter().all(|m| m.applied));
+        assert!(store.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_legacy_tokens() {
+        let mut db = Connection::open_in_memory().unwrap();
+        migrations::up(&mut db, &migrations::SQLITE[..2]).unwrap();
+        db.execute_batch(
+            "INSERT INTO cryptograms (token, plaintext) VALUES (-1234, 'Attack at dawn');
+            INSERT INTO hints (token, kind, position, penalty) VALUES (-1234, 'Letter', 0, 50);",
+        )
+        .unwrap();
+
+        let store = SqliteStore::new(db).unwrap();
+        store.migrate().unwrap();
+
+        let token = "-1234".parse().unwrap();
+        let stored = store.get(&token).unwrap().unwrap();
+        assert_eq!(stored.plaintext, "Attack at dawn");
+        assert_eq!(store.hint_penalty(&token).unwrap(), 50);
+    }
+}
@@ -0,0 +1,264 @@
+//! This module assembles cryptograms into tests, such as a full Codebusters test.
+
+use super::ciphers::CipherResult;
+use super::cryptogram::{self, Cryptogram, Length, Type};
+use super::difficulty::DifficultyRange;
+use super::grading;
+use super::quotes::{Quote, QuoteFilter};
+use super::store::CryptogramStore;
+use super::token::Token;
+use juniper::serde::Deserialize;
+use rand::prelude::*;
+use std::collections::HashSet;
+
+/// Describe one question of a test.
+///
+/// Every option is optional, and unset options use the same defaults as the `cryptogram`
+/// mutation. Specs can also be read from JSON, with the same field names as the GraphQL input.
+#[derive(GraphQLInputObject, Deserialize, Debug, Default, Clone)]
+#[serde(crate = "juniper::serde", rename_all = "camelCase", default)]
+pub struct QuestionSpec {
+    /// The type of cipher.
+    pub r#type: Option<Type>,
+    /// The length of the quote.
+    pub length: Option<Length>,
+    /// The key or keyword, if the type needs one.
+    pub key: Option<String>,
+    /// The points for a perfect answer. Defaults to 250.
+    pub points: Option<i32>,
+    /// A hint printed with the question, such as a crib.
+    pub hint: Option<String>,
+    /// The range of difficulty of the cryptogram.
+    pub difficulty: Option<DifficultyRange>,
+    /// Constraints on the quote, such as its genre.
+    pub quote_filter: Option<QuoteFilter>,
+}
+
+/// A question of a test.
+#[derive(GraphQLObject, Debug)]
+pub struct Question {
+    /// The question number, starting from 1.
+    pub number: i32,
+    /// Token to request the plaintext or submit an answer.
+    pub token: Token,
+    /// The type of cipher used.
+    pub r#type: Type,
+    /// The encrypted text.
+    pub ciphertext: String,
+    /// The points for a perfect answer.
+    pub points: i32,
+    /// A hint printed with the question.
+    pub hint: Option<String>,
+}
+
+impl Question {
+    pub(crate) fn new(number: i32, cryptogram: &Cryptogram, spec: &QuestionSpec) -> Self {
+        Self {
+            number,
+            token: cryptogram.token.clone(),
+            r#type: cryptogram.r#type,
+            ciphertext: cryptogram.ciphertext.clone(),
+            points: spec.points.unwrap_or(grading::DEFAULT_POINTS),
+            hint: spec.hint.clone(),
+        }
+    }
+}
+
+/// A test made of several cryptograms.
+#[derive(GraphQLObject, Debug)]
+pub struct Test {
+    /// The ID to request the test again.
+    pub id: i32,
+    /// The questions, in order.
+    pub questions: Vec<Question>,
+    /// The sum of the points of every question.
+    pub total_points: i32,
+}
+
+impl Test {
+    #[must_use]
+    pub fn new(id: i32, questions: Vec<Question>) -> Self {
+        let total_points = questions.iter().map(|q| q.points).sum();
+        Self {
+            id,
+            questions,
+            total_points,
+        }
+    }
+}
+
+/// Generate a cryptogram for each of `specs`, in order, without using a quote twice.
+///
+/// Random plaintexts are picked from `quotes`, skipping the quotes in `served` while there are
+/// others, as in [`cryptogram::Options`].
+///
+/// The seed of each cryptogram is drawn from an RNG seeded with `seed`, so the same `seed` and
+/// `specs` give the same cryptograms. Errors blame the question they come from, as in
+/// `questions.0.key`.
+pub(crate) fn generate(
+    specs: &[QuestionSpec],
+    seed: i32,
+    quotes: &[Quote],
+    served: Option<&HashSet<i32>>,
+) -> CipherResult<Vec<Cryptogram>> {
+    let rng = &mut StdRng::seed_from_u64(seed as u64);
+    let mut used = HashSet::new();
+
+    let mut cryptograms = Vec::with_capacity(specs.len());
+    for (i, spec) in specs.iter().enumerate() {
+        let cryptogram = Cryptogram::new(cryptogram::Options {
+            quotes,
+            length: spec.length,
+            r#type: spec.r#type,
+            key: spec.key.clone(),
+            seed: Some(rng.gen()),
+            difficulty: spec.difficulty.clone(),
+            quote_filter: spec.quote_filter.clone(),
+            served,
+            used: Some(&used),
+            ..cryptogram::Options::default()
+        })
+        .map_err(|e| e.within(&format!("questions.{i}")))?;
+
+        used.extend(cryptogram.quote_id);
+        cryptograms.push(cryptogram);
+    }
+
+    Ok(cryptograms)
+}
+
+/// Generate a test for `specs` as in [`generate`], store it in `store` with its questions
+/// numbered from 1, and return it with its cryptograms.
+pub(crate) fn create(
+    store: &dyn CryptogramStore,
+    specs: &[QuestionSpec],
+    seed: i32,
+    quotes: &[Quote],
+    served: Option<&HashSet<i32>>,
+) -> CipherResult<(Test, Vec<Cryptogram>)> {
+    let cryptograms = generate(specs, seed, quotes, served)?;
+
+    let questions: Vec<_> = cryptograms
+        .iter()
+        .zip(specs)
+        .enumerate()
+        .map(|(i, (cryptogram, spec))| Question::new(i as i32 + 1, cryptogram, spec))
+        .collect();
+
+    let id = store.insert_test(
+        seed,
+        &cryptograms.iter().zip(&questions).collect::<Vec<_>>(),
+    )?;
+
+    Ok((Test::new(id, questions), cryptograms))
+}
+
+/// Look up test `id` in `store`, or `None` if there is no such test.
+pub(crate) fn load(store: &dyn CryptogramStore, id: i32) -> CipherResult<Option<Test>> {
+    let questions: Vec<_> = store.test(id)?.into_iter().map(|q| q.question).collect();
+
+    Ok((!questions.is_empty()).then(|| Test::new(id, questions)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quotes::QuoteInput;
+    use crate::store::MemoryStore;
+
+    /// `n` different short quotes.
+    fn quotes(n: i32) -> Vec<Quote> {
+        (1..=n)
+            .map(|id| {
+                Quote::new(
+                    id,
+                    QuoteInput {
+                        text: format!("Quote number {id} ").repeat(4),
+                        ..QuoteInput::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// A short question of each of `types`, in order.
+    fn short_specs(types: &[Type]) -> Vec<QuestionSpec> {
+        types
+            .iter()
+            .map(|&r#type| QuestionSpec {
+                r#type: Some(r#type),
+                length: Some(Length::Short),
+                ..QuestionSpec::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_reproducible() {
+        let quotes = quotes(5);
+        let specs = short_specs(&[Type::Aristocrat, Type::Caesar, Type::Patristocrat]);
+
+        let first = generate(&specs, 42, &quotes, None).unwrap();
+        let second = generate(&specs, 42, &quotes, None).unwrap();
+
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.quote_id, b.quote_id);
+            assert_eq!(a.ciphertext, b.ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_generate_no_repeats() {
+        let quotes = quotes(3);
+        let specs = short_specs(&[Type::Identity; 3]);
+
+        for seed in 0..10 {
+            let cryptograms = generate(&specs, seed, &quotes, None).unwrap();
+            let used: HashSet<_> = cryptograms.iter().map(|c| c.quote_id).collect();
+            assert_eq!(used.len(), 3);
+        }
+
+        let specs = short_specs(&[Type::Identity; 4]);
+        let err = generate(&specs, 0, &quotes, None).map(|_| ()).unwrap_err();
+        assert!(err.to_string().starts_with("NoQuoteAvailable"));
+    }
+
+    #[test]
+    fn test_create_and_load() {
+        let store = MemoryStore::default();
+        let mut specs = short_specs(&[Type::Caesar, Type::Identity, Type::Aristocrat]);
+        specs[1].points = Some(400);
+
+        let (test, cryptograms) = create(&store, &specs, 7, &quotes(5), None).unwrap();
+        assert_eq!(test.total_points, 900);
+
+        let loaded = load(&store, test.id).unwrap().unwrap();
+        assert_eq!(loaded.total_points, test.total_points);
+        for (i, (question, cryptogram)) in loaded.questions.iter().zip(&cryptograms).enumerate() {
+            assert_eq!(question.number, i as i32 + 1);
+            assert_eq!(
+                question.r#type.to_string(),
+                specs[i].r#type.unwrap().to_string()
+            );
+            assert_eq!(question.token, cryptogram.token);
+            assert_eq!(question.ciphertext, cryptogram.ciphertext);
+        }
+
+        assert!(load(&store, test.id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_total_points() {
+        let question = |number, points| Question {
+            number,
+            token: Token::generate(),
+            r#type: Type::Aristocrat,
+            ciphertext: String::new(),
+            points,
+            hint: None,
+        };
+
+        let test = Test::new(1, vec![question(1, 250), question(2, 400)]);
+        assert_eq!(test.total_points, 650);
+    }
+}
@@ -0,0 +1,148 @@
+//! This module defines the tokens that identify cryptograms.
+//!
+//! A token is the only thing needed to read a cryptogram's answer, so tokens are 128 random bits,
+//! written as 26 lowercase base32 characters. They do not depend on the cryptogram, so they can't
+//! be guessed from a seed or a ciphertext, and two cryptograms never share one.
+//!
+//! Tokens used to be 32-bit hashes of the ciphertext. Those are still accepted, as their decimal
+//! digits, so cryptograms created before keep working.
+
+use juniper::parser::ScalarToken;
+use juniper::{ParseScalarResult, ParseScalarValue, Value};
+use rand::{thread_rng, Rng};
+use std::{error::Error, fmt, str::FromStr};
+
+/// The characters of a token, from RFC 4648 base32 in lowercase.
+const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// The length of a token, enough for 128 bits at 5 bits per character.
+const LENGTH: usize = 26;
+
+/// An opaque token to request the answer to a cryptogram.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token(String);
+
+impl Token {
+    /// Create a new random token.
+    ///
+    /// [`thread_rng`] is a cryptographically secure RNG, so tokens can't be predicted from the
+    /// ones handed out before.
+    #[must_use]
+    pub fn generate() -> Self {
+        let mut bits: u128 = thread_rng().gen();
+        let mut token = String::with_capacity(LENGTH);
+        for _ in 0..LENGTH {
+            token.push(ALPHABET[(bits & 0b11111) as usize] as char);
+            bits >>= 5;
+        }
+
+        Self(token)
+    }
+
+    /// A token read back from storage, which was valid when it was stored.
+    pub(crate) fn from_stored(token: String) -> Self {
+        Self(token)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Tokens issued before tokens were random.
+impl From<i32> for Token {
+    fn from(token: i32) -> Self {
+        Self(token.to_string())
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A string that is not a token.
+#[derive(Debug)]
+pub struct ParseTokenError(String);
+
+impl Error for ParseTokenError {}
+
+impl fmt::Display for ParseTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid token {:?}", self.0)
+    }
+}
+
+impl FromStr for Token {
+    type Err = ParseTokenError;
+
+    /// Parse a random token, or the decimal digits of a token issued before tokens were random.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(legacy) = s.parse::<i32>() {
+            return Ok(legacy.into());
+        }
+
+        let token = s.to_ascii_lowercase();
+        if token.len() == LENGTH && token.bytes().all(|b| ALPHABET.contains(&b)) {
+            Ok(Self(token))
+        } else {
+            Err(ParseTokenError(s.into()))
+        }
+    }
+}
+
+/// Tokens are strings in GraphQL. Integers are accepted too, so clients that stored integer tokens
+/// keep working.
+#[graphql_scalar(description = "An opaque token to request the answer to a cryptogram.")]
+impl<S> GraphQLScalar for Token
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        Value::scalar(self.0.clone())
+    }
+
+    fn from_input_value(value: &InputValue) -> Option<Token> {
+        match value.as_int_value() {
+            Some(legacy) => Some(legacy.into()),
+            None => value.as_string_value()?.parse().ok(),
+        }
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a, S> {
+        match value {
+            ScalarToken::Int(_) => <i32 as ParseScalarValue<S>>::from_str(value),
+            _ => <String as ParseScalarValue<S>>::from_str(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let token = Token::generate();
+        assert_eq!(token.as_str().len(), LENGTH);
+        assert_eq!(token.as_str().parse::<Token>().unwrap(), token);
+        assert_ne!(Token::generate(), token);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+                .parse::<Token>()
+                .unwrap()
+                .as_str(),
+            "abcdefghijklmnopqrstuvwxyz"
+        );
+        assert_eq!("-1234".parse::<Token>().unwrap(), Token::from(-1234));
+
+        assert!("abc".parse::<Token>().is_err());
+        assert!("abcdefghijklmnopqrstuvwxy1".parse::<Token>().is_err());
+    }
+}
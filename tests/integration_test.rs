@@ -12,6 +12,7 @@ extern crate lazy_static;
 use graphql_client::{reqwest::post_graphql_blocking as post_graphql, GraphQLQuery};
 
 use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use std::io::set_output_capture;
 use std::panic::catch_unwind;
 use std::process::ExitCode;
@@ -20,11 +21,31 @@ use std::thread;
 use tempfile::TempPath;
 
 const URL: &str = "http://localhost:8080/graphql";
+const ADMIN_KEY: &str = "admin-key";
+const PROCTOR_KEY: &str = "proctor-key";
+const STUDENT_KEY: &str = "student-key";
 const TEST_QUOTE: &str = "The quick brown fox jumps over the lazy dog. Can't-I'm<>12932. Cwm fjord bank glyphs vext quiz!";
 
 lazy_static! {
-    /// Client for making requests to the API
-    static ref CLIENT: Client = Client::new();
+    /// Client for making requests to the API as a student
+    static ref CLIENT: Client = client(Some(STUDENT_KEY));
+    /// Client for making requests to the API as a proctor
+    static ref PROCTOR: Client = client(Some(PROCTOR_KEY));
+    /// Client for making requests to the API as an admin
+    static ref ADMIN: Client = client(Some(ADMIN_KEY));
+}
+
+/// Create a client that sends `key` with every request.
+fn client(key: Option<&str>) -> Client {
+    let mut headers = HeaderMap::new();
+    if let Some(key) = key {
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {key}")).unwrap(),
+        );
+    }
+
+    Client::builder().default_headers(headers).build().unwrap()
 }
 
 /// Stores tuples of test and test name
@@ -34,6 +55,15 @@ static TESTS: &[(fn(), &str)] = &[
         test_cryptogram_identity_medium,
         "test_cryptogram_identity_medium",
     ),
+    (test_cryptogram_difficulty, "test_cryptogram_difficulty"),
+    (test_seed_student, "test_seed_student"),
+    (test_seed_proctor, "test_seed_proctor"),
+    (test_history_roles, "test_history_roles"),
+    (test_answer_proctor, "test_answer_proctor"),
+    (test_answer_student, "test_answer_student"),
+    (test_answer_unknown_token, "test_answer_unknown_token"),
+    (test_missing_key, "test_missing_key"),
+    (test_add_quote, "test_add_quote"),
     //    (test_cryptogram_cryptarithm, "test_cryptogram_cryptarithm"),
 ];
 
@@ -158,6 +188,12 @@ fn setup() -> (Vec<TempPath>, Arc<Mutex<Vec<u8>>>) {
     std::fs::write(words_path, r"send,money,more".as_bytes()).unwrap();
 
     std::env::set_var("WORDS_FILE", words_path);
+
+    // keep everything in memory so the tests don't need a database
+    std::env::set_var("STORAGE", "memory");
+    std::env::set_var("ADMIN_KEYS", ADMIN_KEY);
+    std::env::set_var("PROCTOR_KEYS", PROCTOR_KEY);
+    std::env::set_var("STUDENT_KEYS", STUDENT_KEY);
     //    std::env::set_var("WORDS_FILE", "words.txt");
 
     let stdout = Arc::new(Mutex::new(Vec::new()));
@@ -165,7 +201,7 @@ fn setup() -> (Vec<TempPath>, Arc<Mutex<Vec<u8>>>) {
     thread::spawn(move || {
         set_output_capture(Some(clone));
 
-        cryptograms::make_server();
+        cryptograms::make_server().unwrap();
     });
 
     // give some time for the server to start up
@@ -178,6 +214,9 @@ fn setup() -> (Vec<TempPath>, Arc<Mutex<Vec<u8>>>) {
     )
 }
 
+/// Tokens are opaque strings.
+type Token = String;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "tests/schema.graphql",
@@ -194,6 +233,46 @@ pub struct Version;
 )]
 pub struct Cryptogram;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "tests/schema.graphql",
+    query_path = "tests/query.graphql",
+    response_derives = "Debug"
+)]
+pub struct Answer;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "tests/schema.graphql",
+    query_path = "tests/query.graphql",
+    response_derives = "Debug"
+)]
+pub struct RatedCryptogram;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "tests/schema.graphql",
+    query_path = "tests/query.graphql",
+    response_derives = "Debug"
+)]
+pub struct SeededCryptogram;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "tests/schema.graphql",
+    query_path = "tests/query.graphql",
+    response_derives = "Debug"
+)]
+pub struct HistoryCryptogram;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "tests/schema.graphql",
+    query_path = "tests/query.graphql",
+    response_derives = "Debug"
+)]
+pub struct AddQuote;
+
 fn test_api_version() {
     let response_body = post_graphql::<Version, _>(&CLIENT, URL, version::Variables).unwrap();
 
@@ -214,10 +293,180 @@ fn test_cryptogram_identity_medium() {
 
     let data: cryptogram::ResponseData = response_body.data.unwrap();
 
-    assert_eq!(
-        data.cryptogram.ciphertext,
-        "The quick brown fox jumps over the lazy dog. Can't-I'm<>12932. Cwm fjord bank glyphs vext quiz!",
+    // ciphertexts are uppercase unless `format` says otherwise
+    assert_eq!(data.cryptogram.ciphertext, TEST_QUOTE.to_uppercase())
+}
+
+fn test_cryptogram_difficulty() {
+    let variables = rated_cryptogram::Variables {
+        plaintext: Some(TEST_QUOTE.into()),
+        type_: Some(rated_cryptogram::Type::ARISTOCRAT),
+    };
+
+    let response_body = post_graphql::<RatedCryptogram, _>(&CLIENT, URL, variables).unwrap();
+
+    // the difficulty is only rated when it is selected
+    let difficulty = response_body.data.unwrap().cryptogram.difficulty.unwrap();
+    assert!((0.0..=1.0).contains(&difficulty));
+}
+
+fn test_seed_student() {
+    let variables = |type_, seed| seeded_cryptogram::Variables {
+        type_: Some(type_),
+        seed,
+    };
+
+    // the seed of a student's cryptogram is hidden
+    let response_body = post_graphql::<SeededCryptogram, _>(
+        &CLIENT,
+        URL,
+        variables(seeded_cryptogram::Type::ARISTOCRAT, None),
+    )
+    .unwrap();
+    assert_eq!(response_body.data.unwrap().cryptogram.seed, None);
+
+    // so it can't be regenerated as an identity cipher, which would give away the plaintext
+    let response_body = post_graphql::<SeededCryptogram, _>(
+        &CLIENT,
+        URL,
+        variables(seeded_cryptogram::Type::IDENTITY, Some(42)),
     )
+    .unwrap();
+    assert!(response_body.data.is_none());
+    assert_eq!(error_code(response_body.errors), Some("FORBIDDEN".into()));
+}
+
+fn test_seed_proctor() {
+    let variables = seeded_cryptogram::Variables {
+        type_: Some(seeded_cryptogram::Type::IDENTITY),
+        seed: Some(42),
+    };
+
+    let response_body = post_graphql::<SeededCryptogram, _>(&PROCTOR, URL, variables).unwrap();
+    let cryptogram = response_body.data.unwrap().cryptogram;
+    assert_eq!(cryptogram.seed, Some(42));
+    assert_eq!(cryptogram.ciphertext, TEST_QUOTE.to_uppercase());
+}
+
+fn test_history_roles() {
+    use history_cryptogram::{HistoryScope, QuoteHistory};
+
+    let variables = |scope| history_cryptogram::Variables {
+        history: Some(QuoteHistory {
+            scope,
+            id: "roles".into(),
+        }),
+    };
+
+    // students can use their own sessions, but not the histories proctors use for tests
+    let response_body =
+        post_graphql::<HistoryCryptogram, _>(&CLIENT, URL, variables(HistoryScope::SESSION))
+            .unwrap();
+    assert!(response_body.data.is_some());
+
+    let response_body =
+        post_graphql::<HistoryCryptogram, _>(&CLIENT, URL, variables(HistoryScope::TEST)).unwrap();
+    assert!(response_body.data.is_none());
+    assert_eq!(error_code(response_body.errors), Some("FORBIDDEN".into()));
+
+    let response_body =
+        post_graphql::<HistoryCryptogram, _>(&PROCTOR, URL, variables(HistoryScope::TEST)).unwrap();
+    assert!(response_body.data.is_some());
+}
+
+/// The `code` extension of the first of `errors`.
+fn error_code(errors: Option<Vec<graphql_client::Error>>) -> Option<String> {
+    let code = errors?
+        .first()?
+        .extensions
+        .as_ref()?
+        .get("code")?
+        .as_str()?
+        .to_owned();
+    Some(code)
+}
+
+/// Create a cryptogram of the test quote as a student, and return its token.
+fn create_cryptogram() -> String {
+    let variables = cryptogram::Variables {
+        plaintext: None,
+        type_: Some(cryptogram::Type::ARISTOCRAT),
+        length: None,
+    };
+
+    let response_body = post_graphql::<Cryptogram, _>(&CLIENT, URL, variables).unwrap();
+    response_body.data.unwrap().cryptogram.token
+}
+
+fn test_answer_proctor() {
+    let token = create_cryptogram();
+
+    let response_body =
+        post_graphql::<Answer, _>(&PROCTOR, URL, answer::Variables { token }).unwrap();
+
+    let data: answer::ResponseData = response_body.data.unwrap();
+    assert_eq!(data.answer.plaintext, TEST_QUOTE);
+}
+
+fn test_answer_student() {
+    let token = create_cryptogram();
+
+    let response_body =
+        post_graphql::<Answer, _>(&CLIENT, URL, answer::Variables { token }).unwrap();
+
+    assert!(response_body.data.is_none());
+    assert_eq!(error_code(response_body.errors), Some("FORBIDDEN".into()));
+}
+
+fn test_answer_unknown_token() {
+    let token = "a".repeat(26);
+
+    let response_body =
+        post_graphql::<Answer, _>(&PROCTOR, URL, answer::Variables { token }).unwrap();
+
+    assert!(response_body.data.is_none());
+    assert_eq!(
+        error_code(response_body.errors),
+        Some("UNKNOWN_TOKEN".into())
+    );
+}
+
+fn test_missing_key() {
+    // the server rejects the request before GraphQL, so there is no JSON response
+    let res = post_graphql::<Version, _>(&client(None), URL, version::Variables);
+    assert!(res.is_err());
+
+    let res = post_graphql::<Version, _>(&client(Some("wrong")), URL, version::Variables);
+    assert!(res.is_err());
+}
+
+fn test_add_quote() {
+    // a short quote, so the medium quote is still the only one of its length
+    let variables = || add_quote::Variables {
+        quote: add_quote::QuoteInput {
+            text: "Brevity is the soul of wit.".into(),
+            author: Some("William Shakespeare".into()),
+            genre: None,
+            source: Some("Hamlet".into()),
+            language: Some("en".into()),
+            tags: Some(vec!["Wit".into()]),
+        },
+    };
+
+    let response_body = post_graphql::<AddQuote, _>(&ADMIN, URL, variables()).unwrap();
+    let quote = response_body.data.unwrap().add_quote;
+    assert_eq!(quote.text, "Brevity is the soul of wit.");
+    assert_eq!(quote.tags, vec!["wit"]);
+    assert!(quote.enabled);
+
+    let response_body = post_graphql::<AddQuote, _>(&ADMIN, URL, variables()).unwrap();
+    assert_eq!(
+        error_code(response_body.errors),
+        Some("INVALID_ARGUMENT".into())
+    );
+
+    let response_body = post_graphql::<AddQuote, _>(&PROCTOR, URL, variables()).unwrap();
+    assert_eq!(error_code(response_body.errors), Some("FORBIDDEN".into()));
 }
 
 /*